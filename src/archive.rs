@@ -0,0 +1,87 @@
+use chrono::naive::NaiveDate;
+use chrono::{Local, TimeZone};
+use globset::GlobSet;
+use std::io::{Cursor, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Archive holding the log files, after decompression (tar.gz is a gzip input holding a tar)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Archive {
+    Tar,
+    Zip,
+}
+
+impl Archive {
+    /// By the magic bytes, None for plain inputs
+    pub fn sniff(head: &[u8]) -> Option<Archive> {
+        if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Some(Archive::Zip)
+        } else if head.get(257..262) == Some(b"ustar") {
+            // POSIX and GNU tar
+            Some(Archive::Tar)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Archive::Tar => "tar",
+            Archive::Zip => "zip",
+        }
+    }
+
+    /// Calls `member` with name, modification time and content of the regular files,
+    /// only those matching `filter` if given. What `member` leaves unread is skipped.
+    /// Err if the archive itself can't be read.
+    pub fn members<R: Read>(
+        &self,
+        mut input: R,
+        filter: Option<&GlobSet>,
+        mut member: impl FnMut(&str, Option<SystemTime>, &mut dyn Read),
+    ) -> Result<(), String> {
+        let selected = |name: &str| filter.is_none_or(|filter| filter.is_match(name));
+        match self {
+            Archive::Tar => {
+                let mut archive = tar::Archive::new(input);
+                for entry in archive.entries().map_err(|e| e.to_string())? {
+                    let mut entry = entry.map_err(|e| e.to_string())?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let name = entry.path().map_err(|e| e.to_string())?.to_string_lossy().into_owned();
+                    if !selected(&name) {
+                        continue;
+                    }
+                    let mtime = entry.header().mtime().ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+                    member(&name, mtime, &mut entry);
+                }
+            }
+            Archive::Zip => {
+                // the directory is at the end, members written with data descriptors
+                // can't be streamed, so the (compressed) archive is held in memory
+                let mut content = Vec::new();
+                input.read_to_end(&mut content).map_err(|e| e.to_string())?;
+                let mut archive = zip::ZipArchive::new(Cursor::new(content)).map_err(|e| e.to_string())?;
+                for idx in 0..archive.len() {
+                    let mut file = archive.by_index(idx).map_err(|e| e.to_string())?;
+                    if !file.is_file() {
+                        continue;
+                    }
+                    let name = file.name().map_err(|e| e.to_string())?.into_owned();
+                    if !selected(&name) {
+                        continue;
+                    }
+                    let mtime = file.last_modified().and_then(|time| {
+                        let naive = NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+                            .and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)?;
+                        // zip stores the local time
+                        Local.from_local_datetime(&naive).earliest().map(SystemTime::from)
+                    });
+                    member(&name, mtime, &mut file);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::Write;
+use std::ops::Range;
+
+use crate::format::{is_digit, two_digits, LogFormat};
+use crate::syslog::MONTHS;
+use crate::{calc_u64, unpack};
+
+// only the head of the line is searched for the timestamp
+const SEARCH_LIMIT: usize = 512;
+
+/// Common/Combined Log Format of web servers:
+/// 127.0.0.1 - frank [26/Jan/2023:09:32:28 +0100] "GET / HTTP/1.1" 200 2326
+///
+/// The timestamp is the first bracket of the line, the span covers its inside.
+pub struct Clf;
+
+impl Clf {
+    // start of the timestamp, behind the '['
+    #[inline(always)]
+    fn start(buf: &[u8]) -> Option<usize> {
+        let head = &buf[..buf.len().min(SEARCH_LIMIT)];
+        let idx = head.iter().position(|b| *b == b'[')? + 1;
+        if buf.len() > idx + 26 && buf[idx + 26] == b']' {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    fn month(buf: &[u8], idx: usize) -> Option<u32> {
+        MONTHS
+            .iter()
+            .position(|m| buf[idx..idx + 3] == m[..])
+            .map(|m| m as u32 + 1)
+    }
+}
+
+impl LogFormat for Clf {
+    fn name(&self) -> &str {
+        "clf"
+    }
+
+    // 26/Jan/2023:09:32:28 +0100
+    fn detect(&self, buf: &[u8]) -> bool {
+        match Clf::start(buf) {
+            Some(idx) => {
+                let b = &buf[idx..idx + 26];
+                (b[2] == b'/')
+                    & (b[6] == b'/')
+                    & (b[11] == b':')
+                    & (b[14] == b':')
+                    & (b[17] == b':')
+                    & (b[20] == b' ')
+                    & ((b[21] == b'+') | (b[21] == b'-'))
+                    & is_digit(b[0])
+                    & is_digit(b[1])
+                    & is_digit(b[7])
+                    & is_digit(b[8])
+                    & is_digit(b[9])
+                    & is_digit(b[10])
+                    & is_digit(b[12])
+                    & is_digit(b[13])
+                    & is_digit(b[15])
+                    & is_digit(b[16])
+                    & is_digit(b[18])
+                    & is_digit(b[19])
+                    & is_digit(b[22])
+                    & is_digit(b[23])
+                    & is_digit(b[24])
+                    & is_digit(b[25])
+            }
+            None => false,
+        }
+    }
+
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        let idx = Clf::start(buf)?;
+        let month = Clf::month(buf, idx + 3)?;
+        let day = two_digits(buf, idx);
+        let year = (two_digits(buf, idx + 7) as i32) * 100 + two_digits(buf, idx + 9) as i32;
+        let hour = two_digits(buf, idx + 12);
+        let minute = two_digits(buf, idx + 15);
+        let second = two_digits(buf, idx + 18);
+        calc_u64(0, second, minute, hour, day, month as i16, year)
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        match Clf::start(buf) {
+            Some(idx) => idx..idx + 26,
+            None => 0..0,
+        }
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        let (year, month, day, hour, minute, second, _) = unpack(date_value);
+        let month = MONTHS[(month as usize).clamp(1, 12) - 1];
+        let month = std::str::from_utf8(month).unwrap_or("???");
+        write!(output, "{day:02}/{month}/{year}:{hour:02}:{minute:02}:{second:02}")
+    }
+
+    fn utc_offset(&self, buf: &[u8]) -> Option<i32> {
+        let idx = Clf::start(buf)? + 21;
+        let offset = (two_digits(buf, idx + 1) as i32) * 3600 + (two_digits(buf, idx + 3) as i32) * 60;
+        Some(if buf[idx] == b'-' { -offset } else { offset })
+    }
+}
@@ -0,0 +1,162 @@
+use flate2::read::MultiGzDecoder;
+use std::cell::Cell;
+use std::io::{BufRead, BufReader, Read};
+use std::rc::Rc;
+
+// size of the read buffers
+const CAPACITY: usize = 262_144;
+
+/// Compression of an input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+    Lz4,
+}
+
+impl Compression {
+    /// By the magic bytes at the start of the content
+    pub fn sniff(head: &[u8]) -> Compression {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if head.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if head.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+            Compression::Lz4
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Name of the cargo feature, too
+    pub fn name(&self) -> &str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+            Compression::Bzip2 => "bzip2",
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    /// Reader of the decompressed input, Err if the decoder isn't compiled in
+    pub fn decoder<'a, R: Read + 'a>(&self, input: R) -> Result<Box<dyn Read + 'a>, String> {
+        match self {
+            Compression::None => Ok(Box::new(input)),
+            // all members, logrotate copytruncate appends to compressed files
+            Compression::Gzip => Ok(Box::new(MultiGzDecoder::new(input))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::read::Decoder::new(input)
+                .map(|decoder| Box::new(decoder) as Box<dyn Read>)
+                .map_err(|e| e.to_string()),
+            // concatenated streams, like xz of appended files
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(input))),
+            // several streams, as written by pbzip2
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Ok(Box::new(bzip2::read::MultiBzDecoder::new(input))),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(Box::new(lz4_flex::frame::FrameDecoder::new(input))),
+            #[allow(unreachable_patterns)]
+            _ => Err(format!("{} support is not compiled in (cargo feature {})", self.name(), self.name())),
+        }
+    }
+}
+
+/// Where decoding an input failed, everything before was processed
+#[derive(Debug)]
+pub struct Corruption {
+    /// decompressed bytes before the error
+    pub decoded: u64,
+    /// bytes read from the input, compressed ones are read ahead by the buffers
+    pub consumed: u64,
+    pub error: String,
+}
+
+/// Line reader of the decompressed input, the compression is taken from the content
+pub struct Decoded<'a> {
+    pub compression: Compression,
+    pub reader: Box<dyn BufRead + 'a>,
+    consumed: Rc<Cell<u64>>,
+}
+
+impl Decoded<'_> {
+    /// Bytes read from the input so far
+    pub fn consumed(&self) -> u64 {
+        self.consumed.get()
+    }
+}
+
+/// Line reader of an input known to be uncompressed, e.g. read from the middle of a file
+pub fn plain<'a, R: Read + 'a>(input: R) -> Decoded<'a> {
+    let consumed = Rc::new(Cell::new(0));
+    let counting = Counting {
+        inner: input,
+        count: consumed.clone(),
+    };
+    Decoded {
+        compression: Compression::None,
+        reader: Box::new(BufReader::with_capacity(CAPACITY, counting)),
+        consumed,
+    }
+}
+
+/// Line reader of an input with a decoder of its own, e.g. positioned by an index
+pub fn decoded_by<'a, R: Read + 'a, D: Read + 'a>(
+    input: R,
+    compression: Compression,
+    decoder: impl FnOnce(Box<dyn Read + 'a>) -> Result<D, String>,
+) -> Result<Decoded<'a>, String> {
+    let consumed = Rc::new(Cell::new(0));
+    let counting = Counting {
+        inner: input,
+        count: consumed.clone(),
+    };
+    Ok(Decoded {
+        compression,
+        reader: Box::new(BufReader::with_capacity(CAPACITY, decoder(Box::new(counting))?)),
+        consumed,
+    })
+}
+
+pub fn reader<'a, R: Read + 'a>(input: R) -> Result<Decoded<'a>, String> {
+    let consumed = Rc::new(Cell::new(0));
+    let counting = Counting {
+        inner: input,
+        count: consumed.clone(),
+    };
+    let mut input = BufReader::with_capacity(CAPACITY, counting);
+    let compression = Compression::sniff(input.fill_buf().unwrap_or_default());
+    let reader: Box<dyn BufRead + 'a> = if compression == Compression::None {
+        Box::new(input)
+    } else {
+        Box::new(BufReader::with_capacity(CAPACITY, compression.decoder(input)?))
+    };
+    Ok(Decoded {
+        compression,
+        reader,
+        consumed,
+    })
+}
+
+// counts the bytes read from the input
+struct Counting<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count.set(self.count.get() + len as u64);
+        Ok(len)
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::naive::NaiveDate;
+use chrono::Duration;
+use std::io::Write;
+use std::ops::Range;
+
+use crate::format::{has_fraction, is_digit, parse_fraction, LogFormat};
+use crate::{normalize_bits, to_naive, DEFAULT_PIVOT_YEAR, MICROS_MASK};
+
+/// Unix epoch at the start of the line, the unit follows from the number of digits:
+/// 1674722048 (seconds, optional fraction 1674722048.828), 1674722048828 (milliseconds),
+/// 1674722048828123 (microseconds). Epoch values are UTC. The number must lie between 2000
+/// and 2100 and be followed by whitespace, ';' or the end of the line, ids and counts aren't taken.
+pub struct Epoch;
+
+// plausible epoch seconds, 2000-01-01 up to 2100-01-01
+const PLAUSIBLE: std::ops::Range<i64> = 946_684_800..4_102_444_800;
+
+impl Epoch {
+    // number of leading digits
+    #[inline(always)]
+    fn digits(buf: &[u8]) -> usize {
+        buf.iter().take(17).take_while(|b| is_digit(**b)).count()
+    }
+
+    // the value in microseconds and the end of the number
+    pub(crate) fn micros_and_end(buf: &[u8]) -> Option<(i64, usize)> {
+        let (micros, end) = Epoch::number(buf)?;
+        let separated = buf.get(end).is_none_or(|b| b.is_ascii_whitespace() || *b == b';');
+        (separated && PLAUSIBLE.contains(&(micros / 1_000_000))).then_some((micros, end))
+    }
+
+    // the leading number in microseconds and its end, by the number of digits
+    fn number(buf: &[u8]) -> Option<(i64, usize)> {
+        let digits = Epoch::digits(buf);
+        if !matches!(digits, 10 | 13 | 16) {
+            return None;
+        }
+        let value: i64 = std::str::from_utf8(&buf[..digits]).ok()?.parse().ok()?;
+        match digits {
+            10 if buf[10..].starts_with(b".") && has_fraction(buf, 10) => {
+                let end = 11 + buf[11..].iter().take_while(|b| is_digit(**b)).count();
+                Some((value * 1_000_000 + parse_fraction(buf, 10) as i64, end))
+            }
+            10 => Some((value * 1_000_000, 10)),
+            13 => Some((value * 1_000, 13)),
+            16 => Some((value, 16)),
+            _ => None,
+        }
+    }
+}
+
+impl LogFormat for Epoch {
+    fn name(&self) -> &str {
+        "epoch"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        Epoch::micros_and_end(buf).is_some()
+    }
+
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        let (micros, _) = Epoch::micros_and_end(buf)?;
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?.and_hms_opt(0, 0, 0)?;
+        Some(normalize_bits(epoch.checked_add_signed(Duration::microseconds(micros))?, DEFAULT_PIVOT_YEAR))
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        match Epoch::micros_and_end(buf) {
+            Some((_, end)) => 0..end,
+            None => 0..0,
+        }
+    }
+
+    // seconds, with milliseconds or microseconds as fraction
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        let seconds = to_naive(date_value).map(|d| d.and_utc().timestamp()).unwrap_or_default();
+        match date_value & MICROS_MASK {
+            0 => write!(output, "{seconds}"),
+            micro if micro.is_multiple_of(1000) => write!(output, "{seconds}.{:03}", micro / 1000),
+            micro => write!(output, "{seconds}.{micro:06}"),
+        }
+    }
+
+    fn utc_offset(&self, _buf: &[u8]) -> Option<i32> {
+        Some(0)
+    }
+}
@@ -0,0 +1,74 @@
+use colored::Colorize;
+use globset::GlobSet;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Input files from the command line: directories are searched recursively,
+/// the files found there are filtered by include and exclude globs on their path.
+/// Files named explicitly are always taken.
+#[derive(Default)]
+pub struct FileSelection {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl FileSelection {
+    pub fn new(include: Option<GlobSet>, exclude: Option<GlobSet>) -> FileSelection {
+        FileSelection { include, exclude }
+    }
+
+    /// Files in argument order, those of a directory sorted by name
+    pub fn expand(&self, paths: &[String]) -> Vec<String> {
+        let mut files = Vec::new();
+        for path in paths {
+            if Path::new(path).is_dir() {
+                self.walk(Path::new(path), &mut files);
+            } else {
+                // missing ones are reported when they are opened
+                files.push(path.clone());
+            }
+        }
+        files
+    }
+
+    fn walk(&self, dir: &Path, files: &mut Vec<String>) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("{}: {}", dir.display().to_string().bold(), e.to_string().red());
+                return;
+            }
+        };
+        let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            if self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(&path)) {
+                continue;
+            }
+            // symlinked directories aren't followed, they may loop
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => self.walk(&path, files),
+                Ok(_) if path.is_file() && self.include.as_ref().is_none_or(|include| include.is_match(&path)) => {
+                    files.push(path.to_string_lossy().into_owned())
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Paths of a --files-from list, one per line or NUL separated (find -print0)
+pub fn read_list(mut input: impl Read, nul: bool) -> std::io::Result<Vec<String>> {
+    let mut content = Vec::new();
+    input.read_to_end(&mut content)?;
+    let separator = if nul { b'\0' } else { b'\n' };
+    Ok(content
+        .split(|b| *b == separator)
+        // lists written on windows
+        .map(|path| if nul { path } else { path.strip_suffix(b"\r").unwrap_or(path) })
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .filter(|path| !path.is_empty())
+        .collect())
+}
@@ -0,0 +1,90 @@
+use std::fs::{self, File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// wait at the end of the file before looking again
+const POLL: Duration = Duration::from_millis(250);
+
+/// Reads a growing file like tail -F: at its end it waits for more. The file is reopened
+/// when it was rotated (another file at the path) and read from the start when truncated.
+/// It ends after `idle` without new data, or never.
+pub struct Tail {
+    path: PathBuf,
+    file: File,
+    position: u64,
+    idle: Option<Duration>,
+    last_data: Instant,
+    debug: u8,
+}
+
+impl Tail {
+    /// Reads on from the current position of `file`
+    pub fn new(path: &str, mut file: File, idle: Option<Duration>, debug: u8) -> std::io::Result<Tail> {
+        let position = file.stream_position()?;
+        Ok(Tail {
+            path: PathBuf::from(path),
+            file,
+            position,
+            idle,
+            last_data: Instant::now(),
+            debug,
+        })
+    }
+
+    // the file at the path, if it's another one than the open file
+    fn rotated(&self) -> Option<File> {
+        let current = self.file.metadata().ok()?;
+        let at_path = fs::metadata(&self.path).ok()?;
+        if same_file(&current, &at_path) {
+            return None;
+        }
+        File::open(&self.path).ok()
+    }
+}
+
+impl Read for Tail {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let len = self.file.read(buf)?;
+            if len > 0 {
+                self.position += len as u64;
+                self.last_data = Instant::now();
+                return Ok(len);
+            }
+            // the old file is read to its end, the rotated one from the start
+            if let Some(file) = self.rotated() {
+                if self.debug > 0 {
+                    eprintln!("{}: rotated, reopened", self.path.display());
+                }
+                self.file = file;
+                self.position = 0;
+                continue;
+            }
+            if self.file.metadata()?.len() < self.position {
+                if self.debug > 0 {
+                    eprintln!("{}: truncated, reading from the start", self.path.display());
+                }
+                self.position = self.file.seek(SeekFrom::Start(0))?;
+                continue;
+            }
+            if self.idle.is_some_and(|idle| self.last_data.elapsed() >= idle) {
+                return Ok(0);
+            }
+            thread::sleep(POLL);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+// rotation isn't seen, truncation is
+#[cfg(not(unix))]
+fn same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
@@ -0,0 +1,801 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::naive::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local};
+use std::io::Write;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+pub use crate::clf::Clf;
+pub use crate::epoch::Epoch;
+pub use crate::json::Json;
+pub use crate::syslog::{Syslog3164, Syslog5424};
+
+use crate::locate::Locator;
+use crate::strict::Rejection;
+use crate::zone::{DstPolicy, Zone};
+use crate::{calc_u64, normalize_bits, pivot_year, unpack, write_fraction, write_to_output, NormRetValue, DEFAULT_PIVOT_YEAR, MICROS_MASK};
+
+/// A log layout which carries a timestamp.
+///
+/// `detect` is the cheap shape check, `parse` does the real work and
+/// returns the packed date value (see `calc_u64`).
+pub trait LogFormat: Send + Sync {
+    /// Name of the format, used in debug output
+    fn name(&self) -> &str;
+
+    /// Cheap check whether the line could carry a timestamp of this format
+    fn detect(&self, buf: &[u8]) -> bool;
+
+    /// Parse the timestamp of a detected line
+    fn parse(&self, buf: &[u8]) -> Option<u64>;
+
+    /// Byte range of the timestamp within the line
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize>;
+
+    /// Write the date value in the layout of this format
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()>;
+
+    /// Offset to UTC in seconds, if the line carries one
+    fn utc_offset(&self, _buf: &[u8]) -> Option<i32> {
+        None
+    }
+
+    /// Parse with the state of the input, for layouts which need more than the line
+    fn parse_with(&self, buf: &[u8], _state: &mut InputState) -> Option<u64> {
+        self.parse(buf)
+    }
+
+    /// Named field of the line, e.g. host, program and pid of syslog lines
+    fn field<'a>(&self, _buf: &'a [u8], _name: &str) -> Option<&'a [u8]> {
+        None
+    }
+
+    /// Whether the replaced timestamp needs quotes, e.g. a JSON number
+    fn quote(&self, _buf: &[u8]) -> bool {
+        false
+    }
+}
+
+/// Where the year of layouts without one comes from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum YearStrategy {
+    /// every line lies before the modification time of the input (or now for stdin)
+    Mtime,
+    /// the first line like Mtime, afterwards the year changes when December turns into January
+    Rollover,
+    /// the first line is in the given year, afterwards like Rollover
+    Start(i32),
+}
+
+impl FromStr for YearStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<YearStrategy, String> {
+        match s {
+            "mtime" => Ok(YearStrategy::Mtime),
+            "rollover" => Ok(YearStrategy::Rollover),
+            _ => match s.parse::<i32>() {
+                Ok(year) if (1000..=4000).contains(&year) => Ok(YearStrategy::Start(year)),
+                _ => Err(format!("invalid year: {s} (mtime, rollover or YYYY)")),
+            },
+        }
+    }
+}
+
+/// State of one input, passed along its lines
+#[derive(Clone)]
+pub struct InputState {
+    /// date value of the line before
+    pub previous: u64,
+    /// modification time of the input or now
+    reference: NaiveDateTime,
+    strategy: YearStrategy,
+    // year and month of the last line without year
+    year: i32,
+    month: u32,
+    // century of two digit years
+    pub(crate) pivot: i32,
+    /// the span of the timestamp is wanted (replace), patterns without width parse the line again for it
+    pub spans: bool,
+    /// only real calendar dates, no hour 24, day 0, month 0 or 31.02.
+    pub strict: bool,
+}
+
+impl InputState {
+    pub fn new(reference: Option<SystemTime>, strategy: YearStrategy) -> InputState {
+        let reference = match reference {
+            Some(time) => DateTime::<Local>::from(time).naive_local(),
+            None => Local::now().naive_local(),
+        };
+        InputState {
+            previous: 0,
+            reference,
+            strategy,
+            year: 0,
+            month: 0,
+            pivot: DEFAULT_PIVOT_YEAR,
+            spans: true,
+            strict: false,
+        }
+    }
+
+    /// Year of a line which only carries month, day and time
+    pub fn infer_year(&mut self, month: u32, day: u32, time: NaiveTime) -> i32 {
+        let year = if self.year == 0 || self.strategy == YearStrategy::Mtime {
+            match self.strategy {
+                YearStrategy::Start(year) => year,
+                _ => {
+                    // a day of tolerance for skewed clocks and zones
+                    let limit = self.reference + Duration::days(1);
+                    let year = self.reference.year();
+                    match NaiveDate::from_ymd_opt(year, month, day) {
+                        Some(date) if date.and_time(time) > limit => year - 1,
+                        _ => year,
+                    }
+                }
+            }
+        } else if month < self.month {
+            self.year + 1
+        } else {
+            self.year
+        };
+        self.year = year;
+        self.month = month;
+        year
+    }
+}
+
+impl Default for InputState {
+    fn default() -> InputState {
+        InputState::new(None, YearStrategy::Mtime)
+    }
+}
+
+/// Ordered set of formats, the first one which detects and parses a line wins.
+/// Formats with a zone are normalized to UTC.
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn LogFormat>>,
+    zones: Vec<Option<Zone>>,
+    dst: DstPolicy,
+    year: YearStrategy,
+    sample: usize,
+    locator: Option<Locator>,
+    pivot: i32,
+}
+
+impl Default for FormatRegistry {
+    fn default() -> FormatRegistry {
+        FormatRegistry {
+            formats: Vec::new(),
+            zones: Vec::new(),
+            dst: DstPolicy::Auto,
+            year: YearStrategy::Mtime,
+            sample: 100,
+            locator: None,
+            pivot: DEFAULT_PIVOT_YEAR,
+        }
+    }
+}
+
+impl FormatRegistry {
+    pub fn new() -> FormatRegistry {
+        FormatRegistry::default()
+    }
+
+    /// Carmen, Carmen-Error, Yoda, ISO 8601, syslog and access logs. `fast` selects the
+    /// byte parsers, otherwise chrono is used (the others have the byte parser only).
+    /// Epoch isn't among them, numbers in payload lines look alike.
+    pub fn builtin(fast: bool) -> FormatRegistry {
+        let mut registry = FormatRegistry::new();
+        if fast {
+            registry.register(Box::new(Yoda));
+            registry.register(Box::new(Iso));
+            registry.register(Box::new(CarmenErr));
+            registry.register(Box::new(Carmen));
+        } else {
+            registry.register(Box::new(ChronoFormat::new("carmen", "%d.%m.%Y %H:%M:%S", 17)));
+            registry.register(Box::new(ChronoFormat::new("yoda", "%Y-%m-%d %H:%M:%S", 19)));
+            registry.register(Box::new(ChronoFormat::new("carmen-err", "%Y%m%d%H%M%S", 14)));
+            registry.register(Box::new(Iso));
+        }
+        registry.register(Box::new(Syslog5424));
+        registry.register(Box::new(Syslog3164));
+        registry.register(Box::new(Clf));
+        registry
+    }
+
+    pub fn register(&mut self, format: Box<dyn LogFormat>) {
+        self.formats.push(format);
+        self.zones.push(None);
+    }
+
+    /// Formats of `other` are tried after the ones already registered
+    pub fn append(&mut self, mut other: FormatRegistry) {
+        self.formats.append(&mut other.formats);
+        self.zones.append(&mut other.zones);
+    }
+
+    /// Zone of the formats called `name`, returns how many formats matched
+    pub fn set_zone(&mut self, name: &str, zone: &Zone) -> usize {
+        let mut count = 0;
+        for (format, slot) in self.formats.iter().zip(self.zones.iter_mut()) {
+            if format.name() == name {
+                *slot = Some(zone.clone());
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Zone of all formats without an explicit one
+    pub fn set_default_zone(&mut self, zone: &Zone) {
+        for slot in self.zones.iter_mut().filter(|slot| slot.is_none()) {
+            *slot = Some(zone.clone());
+        }
+    }
+
+    pub fn set_dst_policy(&mut self, dst: DstPolicy) {
+        self.dst = dst;
+    }
+
+    pub fn set_year_strategy(&mut self, year: YearStrategy) {
+        self.year = year;
+    }
+
+    /// Lines of an input sampled to pick its dominant format, 0 detects on every line
+    pub fn set_sample_lines(&mut self, lines: usize) {
+        self.sample = lines;
+    }
+
+    pub fn sample_lines(&self) -> usize {
+        self.sample
+    }
+
+    /// Where the timestamp starts, for lines with a prefix
+    pub fn set_locator(&mut self, locator: Locator) {
+        self.locator = Some(locator);
+    }
+
+    // index of the timestamp
+    #[inline(always)]
+    fn locate(&self, buf: &[u8]) -> Option<usize> {
+        match &self.locator {
+            Some(locator) => locator.start(buf),
+            None => Some(0),
+        }
+    }
+
+    /// Two digit years fall into the century starting at `pivot`
+    pub fn set_pivot_year(&mut self, pivot: i32) {
+        self.pivot = pivot;
+    }
+
+    /// Everything the date values depend on besides the lines: formats and their zones, DST
+    /// policy, years, locator and the local zone. Values of other settings don't compare.
+    pub fn settings(&self) -> String {
+        let zones: Vec<String> = self.formats.iter().zip(&self.zones).map(|(format, zone)| format!("{}={:?}", format.name(), zone)).collect();
+        format!(
+            "{};dst={:?};year={:?};pivot={};locator={:?};local={}",
+            zones.join(","),
+            self.dst,
+            self.year,
+            self.pivot,
+            self.locator,
+            std::env::var("TZ").unwrap_or_default()
+        )
+    }
+
+    /// Why a line wasn't normalized in strict mode. Formats which reject invalid dates
+    /// themselves (chrono) fall back to the byte parsers with the same pivot, so both classify alike.
+    pub fn rejection(&self, buf: &[u8]) -> Rejection {
+        let buf = match self.locate(buf) {
+            Some(start) => &buf[start..],
+            None => return Rejection::NoTimestamp,
+        };
+        let mut state = self.input_state(None);
+        self.formats
+            .iter()
+            .filter(|format| format.detect(buf))
+            .find_map(|format| format.parse_with(buf, &mut state))
+            .or_else(|| {
+                let parsers: [&dyn LogFormat; 4] = [&Yoda, &Iso, &CarmenErr, &Carmen];
+                let parser = parsers.into_iter().find(|parser| quick_check(buf) && parser.detect(buf))?;
+                parser.parse_with(buf, &mut state)
+            })
+            .and_then(Rejection::of)
+            .unwrap_or(Rejection::NoTimestamp)
+    }
+
+    /// State for an input modified at `reference` (None for stdin)
+    pub fn input_state(&self, reference: Option<SystemTime>) -> InputState {
+        let mut state = InputState::new(reference, self.year);
+        state.pivot = self.pivot;
+        state
+    }
+
+    pub fn formats(&self) -> &[Box<dyn LogFormat>] {
+        &self.formats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+
+    pub fn normalize(&self, buf: &[u8]) -> Option<NormRetValue> {
+        self.normalize_in(buf, &mut self.input_state(None))
+    }
+
+    /// Named field of the line, from the first format which detects it
+    pub fn field<'a>(&self, buf: &'a [u8], name: &str) -> Option<&'a [u8]> {
+        let buf = &buf[self.locate(buf)?..];
+        self.formats.iter().find(|format| format.detect(buf))?.field(buf, name)
+    }
+
+    /// Like `normalize` with the state of the input, remembers the date value
+    /// as `previous` which is needed to place lines of a repeated DST hour
+    pub fn normalize_in(&self, buf: &[u8], state: &mut InputState) -> Option<NormRetValue> {
+        self.detect_in(buf, state).map(|(_, value)| value)
+    }
+
+    /// Like `normalize_in`, also returns the index of the format which parsed the line
+    pub fn detect_in(&self, buf: &[u8], state: &mut InputState) -> Option<(usize, NormRetValue)> {
+        let start = self.locate(buf)?;
+        (0..self.formats.len()).find_map(|idx| self.normalize_at(idx, buf, start, state).map(|value| (idx, value)))
+    }
+
+    /// Normalize with the format at `idx` only
+    pub fn normalize_by(&self, idx: usize, buf: &[u8], state: &mut InputState) -> Option<NormRetValue> {
+        self.normalize_at(idx, buf, self.locate(buf)?, state)
+    }
+
+    // the timestamp starts at `start`, the span is relative to the whole line
+    fn normalize_at(&self, idx: usize, line: &[u8], start: usize, state: &mut InputState) -> Option<NormRetValue> {
+        let buf = &line[start..];
+        let format = &self.formats[idx];
+        if !format.detect(buf) {
+            return None;
+        }
+        let date_value = format.parse_with(buf, state)?;
+        if state.strict && Rejection::of(date_value).is_some() {
+            return None;
+        }
+        let date_value = match (&self.zones[idx], format.utc_offset(buf)) {
+            (Some(_), Some(offset)) => Zone::Fixed(FixedOffset::east_opt(offset)?).to_utc(date_value, self.dst, 0)?,
+            (Some(zone), None) => zone.to_utc(date_value, self.dst, state.previous)?,
+            // without zones everything is local wall clock, like the lines without offset
+            (None, Some(offset)) => {
+                let utc = Zone::Fixed(FixedOffset::east_opt(offset)?).to_utc(date_value, self.dst, 0)?;
+                Zone::Local.from_utc(utc)?
+            }
+            (None, None) => date_value,
+        };
+        state.previous = date_value;
+        let span = if state.spans { format.prefix_span(buf) } else { 0..0 };
+        Some(NormRetValue {
+            date_value,
+            span: start + span.start..start + span.end,
+            quote: format.quote(buf),
+        })
+    }
+}
+
+#[inline(always)]
+pub fn normalize_with(format: &dyn LogFormat, buf: &[u8]) -> Option<NormRetValue> {
+    if !format.detect(buf) {
+        return None;
+    }
+    let date_value = format.parse(buf)?;
+    Some(NormRetValue {
+        date_value,
+        span: format.prefix_span(buf),
+        quote: format.quote(buf),
+    })
+}
+
+#[inline(always)]
+pub(crate) fn is_digit(b: u8) -> bool {
+    b.is_ascii_digit()
+}
+
+#[inline(always)]
+pub(crate) fn two_digits(buf: &[u8], idx: usize) -> i16 {
+    ((buf[idx] as i16) - 48) * 10 + ((buf[idx + 1] as i16) - 48)
+}
+
+// common to all builtin layouts, digits at 0, 1, 3, 6, 9 and 12
+#[inline(always)]
+pub(crate) fn quick_check(buf: &[u8]) -> bool {
+    (buf.len() > 14)
+        && (is_digit(buf[0])
+            & is_digit(buf[1])
+            & is_digit(buf[3])
+            & is_digit(buf[6])
+            & is_digit(buf[9])
+            & is_digit(buf[12]))
+}
+
+// separator followed by at least one digit
+#[inline(always)]
+pub(crate) fn has_fraction(buf: &[u8], idx: usize) -> bool {
+    (buf.len() > idx + 1) && ((buf[idx] == b',') | (buf[idx] == b'.')) && is_digit(buf[idx + 1])
+}
+
+// ,SSS .SSS .SSSSSS behind the seconds, in microseconds
+#[inline(always)]
+pub(crate) fn parse_fraction(buf: &[u8], idx: usize) -> u32 {
+    if !has_fraction(buf, idx) {
+        return 0;
+    }
+    let mut micro: u32 = 0;
+    let mut scale: u32 = 100_000;
+    for &b in buf[idx + 1..].iter().take_while(|b| is_digit(**b)) {
+        micro += ((b - 48) as u32) * scale;
+        scale /= 10;
+    }
+    micro
+}
+
+// YYYY-MM-DD?hh:mm:ss[,SSS]
+#[inline(always)]
+fn parse_ymd_hms(buf: &[u8]) -> Option<u64> {
+    let year = (two_digits(buf, 0) as i32) * 100 + two_digits(buf, 2) as i32;
+    let month = two_digits(buf, 5);
+    let day = two_digits(buf, 8);
+    let hour = two_digits(buf, 11);
+    let minute = two_digits(buf, 14);
+    let second = two_digits(buf, 17);
+    calc_u64(parse_fraction(buf, 19), second, minute, hour, day, month, year)
+}
+
+fn write_two_digits(chars: &mut [u8], idx: usize, value: u64) {
+    chars[idx] = (value / 10) as u8 + 48;
+    chars[idx + 1] = (value % 10) as u8 + 48;
+}
+
+/// 2023-01-26 09:32:28
+pub struct Yoda;
+
+impl LogFormat for Yoda {
+    fn name(&self) -> &str {
+        "yoda"
+    }
+
+    #[inline(always)]
+    fn detect(&self, buf: &[u8]) -> bool {
+        quick_check(buf)
+            && (buf.len() >= 19)
+            && ((buf[4] == 45) & (buf[7] == 45) & (buf[10] == 32) & (buf[13] == 58) & (buf[16] == 58))
+            && (is_digit(buf[2])
+                & is_digit(buf[5])
+                & is_digit(buf[8])
+                & is_digit(buf[11])
+                & is_digit(buf[14])
+                & is_digit(buf[15])
+                & is_digit(buf[17])
+                & is_digit(buf[18]))
+    }
+
+    #[inline(always)]
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        parse_ymd_hms(buf)
+    }
+
+    fn prefix_span(&self, _buf: &[u8]) -> Range<usize> {
+        0..19
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        write_to_output(output, date_value)?;
+        if date_value & MICROS_MASK != 0 {
+            write_fraction(output, date_value, b',')?;
+        }
+        Ok(())
+    }
+}
+
+/// 2023-01-26T09:32:28.828+01:00, fraction and offset (Z, +01:00, +0100, +01) are optional
+pub struct Iso;
+
+impl Iso {
+    // offset behind the seconds and fraction, and where the timestamp ends
+    pub(crate) fn offset_and_end(buf: &[u8]) -> (Option<i32>, usize) {
+        let mut idx = 19;
+        if has_fraction(buf, idx) {
+            idx += 1;
+            while idx < buf.len() && is_digit(buf[idx]) {
+                idx += 1;
+            }
+        }
+        if idx >= buf.len() {
+            return (None, idx);
+        }
+        if (buf[idx] == b'Z') | (buf[idx] == b'z') {
+            return (Some(0), idx + 1);
+        }
+        if ((buf[idx] == b'+') | (buf[idx] == b'-')) && (buf.len() > idx + 2) && is_digit(buf[idx + 1]) & is_digit(buf[idx + 2]) {
+            let hours = two_digits(buf, idx + 1) as i32;
+            let mut end = idx + 3;
+            let mut minutes = 0;
+            if (buf.len() > end + 2) && (buf[end] == b':') && is_digit(buf[end + 1]) & is_digit(buf[end + 2]) {
+                minutes = two_digits(buf, end + 1) as i32;
+                end += 3;
+            } else if (buf.len() > end + 1) && is_digit(buf[end]) & is_digit(buf[end + 1]) {
+                minutes = two_digits(buf, end) as i32;
+                end += 2;
+            }
+            if (hours <= 23) & (minutes <= 59) {
+                let offset = hours * 3600 + minutes * 60;
+                return (Some(if buf[idx] == b'-' { -offset } else { offset }), end);
+            }
+        }
+        (None, idx)
+    }
+}
+
+impl LogFormat for Iso {
+    fn name(&self) -> &str {
+        "iso"
+    }
+
+    #[inline(always)]
+    fn detect(&self, buf: &[u8]) -> bool {
+        quick_check(buf)
+            && (buf.len() >= 19)
+            && ((buf[4] == 45) & (buf[7] == 45) & ((buf[10] == b'T') | (buf[10] == b't')) & (buf[13] == 58) & (buf[16] == 58))
+            && (is_digit(buf[2])
+                & is_digit(buf[5])
+                & is_digit(buf[8])
+                & is_digit(buf[11])
+                & is_digit(buf[14])
+                & is_digit(buf[15])
+                & is_digit(buf[17])
+                & is_digit(buf[18]))
+    }
+
+    #[inline(always)]
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        parse_ymd_hms(buf)
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        0..Iso::offset_and_end(buf).1
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        let mut chars: Vec<u8> = Vec::with_capacity(26);
+        write_to_output(&mut chars, date_value)?;
+        chars[10] = b'T';
+        if date_value & MICROS_MASK != 0 {
+            write_fraction(&mut chars, date_value, b'.')?;
+        }
+        output.write_all(&chars)
+    }
+
+    fn utc_offset(&self, buf: &[u8]) -> Option<i32> {
+        Iso::offset_and_end(buf).0
+    }
+}
+
+/// 20230729111238
+pub struct CarmenErr;
+
+impl LogFormat for CarmenErr {
+    fn name(&self) -> &str {
+        "carmen-err"
+    }
+
+    #[inline(always)]
+    fn detect(&self, buf: &[u8]) -> bool {
+        quick_check(buf)
+            && (is_digit(buf[2])
+                & is_digit(buf[4])
+                & is_digit(buf[5])
+                & is_digit(buf[7])
+                & is_digit(buf[8])
+                & is_digit(buf[10])
+                & is_digit(buf[11])
+                & is_digit(buf[13]))
+    }
+
+    #[inline(always)]
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        let year = (two_digits(buf, 0) as i32) * 100 + two_digits(buf, 2) as i32;
+        let month = two_digits(buf, 4);
+        let day = two_digits(buf, 6);
+        let hour = two_digits(buf, 8);
+        let minute = two_digits(buf, 10);
+        let second = two_digits(buf, 12);
+        calc_u64(0, second, minute, hour, day, month, year)
+    }
+
+    fn prefix_span(&self, _buf: &[u8]) -> Range<usize> {
+        0..14
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        let (year, month, day, hour, minute, second, _) = unpack(date_value);
+        let mut chars: [u8; 14] = Default::default();
+        write_two_digits(&mut chars, 0, year / 100);
+        write_two_digits(&mut chars, 2, year % 100);
+        write_two_digits(&mut chars, 4, month);
+        write_two_digits(&mut chars, 6, day);
+        write_two_digits(&mut chars, 8, hour);
+        write_two_digits(&mut chars, 10, minute);
+        write_two_digits(&mut chars, 12, second);
+        output.write_all(&chars)
+    }
+}
+
+/// 24.12.22 00:02:05
+pub struct Carmen;
+
+impl Carmen {
+    #[inline(always)]
+    fn parse_pivot(buf: &[u8], pivot: i32) -> Option<u64> {
+        let year = pivot_year(two_digits(buf, 6) as i32, pivot);
+        let month = two_digits(buf, 3);
+        let day = two_digits(buf, 0);
+        let hour = two_digits(buf, 9);
+        let minute = two_digits(buf, 12);
+        let second = two_digits(buf, 15);
+        calc_u64(parse_fraction(buf, 17), second, minute, hour, day, month, year)
+    }
+}
+
+impl LogFormat for Carmen {
+    fn name(&self) -> &str {
+        "carmen"
+    }
+
+    #[inline(always)]
+    fn detect(&self, buf: &[u8]) -> bool {
+        quick_check(buf)
+            && (buf.len() >= 17)
+            && ((buf[2] == 46) & (buf[5] == 46) & (buf[8] == 32) & (buf[11] == 58) & (buf[14] == 58))
+            && (is_digit(buf[4])
+                & is_digit(buf[7])
+                & is_digit(buf[10])
+                & is_digit(buf[13])
+                & is_digit(buf[15])
+                & is_digit(buf[16]))
+    }
+
+    #[inline(always)]
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        Carmen::parse_pivot(buf, DEFAULT_PIVOT_YEAR)
+    }
+
+    fn parse_with(&self, buf: &[u8], state: &mut InputState) -> Option<u64> {
+        Carmen::parse_pivot(buf, state.pivot)
+    }
+
+    fn prefix_span(&self, _buf: &[u8]) -> Range<usize> {
+        0..17
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        let (year, month, day, hour, minute, second, _) = unpack(date_value);
+        let mut chars: [u8; 17] = *b"dd.mm.yy hh:mm:ss";
+        write_two_digits(&mut chars, 0, day);
+        write_two_digits(&mut chars, 3, month);
+        write_two_digits(&mut chars, 6, year % 100);
+        write_two_digits(&mut chars, 9, hour);
+        write_two_digits(&mut chars, 12, minute);
+        write_two_digits(&mut chars, 15, second);
+        output.write_all(&chars)
+    }
+}
+
+/// Layout parsed by chrono. Either fixed width (`len` bytes) or as far
+/// as the pattern consumes, starting at byte `offset` of the line.
+pub struct ChronoFormat {
+    name: String,
+    pattern: String,
+    offset: usize,
+    len: Option<usize>,
+}
+
+impl ChronoFormat {
+    pub fn new(name: &str, pattern: &str, len: usize) -> ChronoFormat {
+        ChronoFormat {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            offset: 0,
+            len: Some(len),
+        }
+    }
+
+    /// User layout from the command line: [OFFSET@]PATTERN
+    /// e.g. "%Y/%m/%d %H:%M:%S" or "16@%d/%b/%Y:%H:%M:%S"
+    pub fn from_spec(spec: &str) -> Result<ChronoFormat, String> {
+        let (offset, pattern) = match spec.split_once('@') {
+            Some((offset, pattern)) if !offset.is_empty() && offset.bytes().all(|b| b.is_ascii_digit()) => {
+                let offset = offset
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid offset in format: {spec}"))?;
+                (offset, pattern)
+            }
+            _ => (0, spec),
+        };
+        if pattern.is_empty() || StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+            return Err(format!("invalid strftime pattern: {pattern}"));
+        }
+        Ok(ChronoFormat {
+            name: spec.to_string(),
+            pattern: pattern.to_string(),
+            offset,
+            len: None,
+        })
+    }
+
+    // the part of the line the pattern is applied to
+    fn window<'a>(&self, buf: &'a [u8]) -> Option<&'a str> {
+        let window = match self.len {
+            Some(len) => &buf[self.offset..self.offset + len],
+            None => &buf[self.offset..buf.len().min(self.offset + 128)],
+        };
+        match std::str::from_utf8(window) {
+            Ok(s) => Some(s),
+            // a multibyte char cut by the window, or garbage behind the timestamp
+            Err(e) => std::str::from_utf8(&window[..e.valid_up_to()]).ok(),
+        }
+    }
+
+    fn parse_naive(&self, buf: &[u8]) -> Option<(NaiveDateTime, usize)> {
+        let line_str = self.window(buf)?;
+        match self.len {
+            Some(len) => {
+                let dt = NaiveDateTime::parse_from_str(line_str, &self.pattern).ok()?;
+                Some((dt, len))
+            }
+            None => {
+                let (dt, rest) = NaiveDateTime::parse_and_remainder(line_str, &self.pattern).ok()?;
+                Some((dt, line_str.len() - rest.len()))
+            }
+        }
+    }
+
+    fn parse_pivot(&self, buf: &[u8], pivot: i32) -> Option<u64> {
+        let (dt, len) = self.parse_naive(buf)?;
+        let mut date_value = normalize_bits(dt, pivot);
+        if date_value & MICROS_MASK == 0 {
+            // fraction not covered by the pattern
+            date_value += parse_fraction(buf, self.offset + len) as u64;
+        }
+        Some(date_value)
+    }
+}
+
+impl LogFormat for ChronoFormat {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        buf.len() >= self.offset + self.len.unwrap_or(1)
+    }
+
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        self.parse_pivot(buf, DEFAULT_PIVOT_YEAR)
+    }
+
+    fn parse_with(&self, buf: &[u8], state: &mut InputState) -> Option<u64> {
+        self.parse_pivot(buf, state.pivot)
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        let len = match self.len {
+            Some(len) => Some(len),
+            None => self.parse_naive(buf).map(|(_, len)| len),
+        };
+        self.offset..self.offset + len.unwrap_or(0)
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        match crate::to_naive(date_value) {
+            Some(dt) => write!(output, "{}", dt.format(&self.pattern)),
+            None => Ok(()),
+        }
+    }
+}
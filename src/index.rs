@@ -0,0 +1,476 @@
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::write::DeflateEncoder;
+use libz_sys as z;
+use std::alloc::{self, Layout};
+use std::ffi::{c_int, c_void};
+use std::fs::File;
+use std::io::{Chain, Cursor, Read, Seek, SeekFrom, Write};
+use std::ptr;
+use std::time::UNIX_EPOCH;
+
+use crate::format::FormatRegistry;
+
+/// Appended to the name of the gzip file
+pub const SUFFIX: &str = ".fdx";
+
+const MAGIC: &[u8; 4] = b"FDX2";
+
+// deflate window, the dictionary to resume with
+const WINDOW: usize = 32_768;
+
+// compressed bytes read at once
+const CHUNK: usize = 65_536;
+
+/// Where inflating can resume, the position of a deflate block in the gzip file
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    /// compressed offset, the block starts `bits` bits before it
+    pub input: u64,
+    pub bits: u8,
+    /// uncompressed offset
+    pub output: u64,
+    /// timestamp of the first line starting behind the checkpoint
+    pub first: Option<u64>,
+    window: Vec<u8>,
+}
+
+/// Sidecar of a gzip file (zran): checkpoints every span of uncompressed bytes with the
+/// 32 KiB of output before them. Size and mtime tell if the gzip file changed since,
+/// `settings` how the timestamps were normalized (see `FormatRegistry::settings`).
+pub struct Index {
+    pub size: u64,
+    pub mtime: u64,
+    pub settings: String,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Index {
+    pub fn sidecar(file_name: &str) -> String {
+        format!("{file_name}{SUFFIX}")
+    }
+
+    /// Inflates the whole file once, a checkpoint after every `span` bytes of output.
+    /// The timestamps are normalized with `formats`
+    pub fn build(file_name: &str, formats: &FormatRegistry, span: u64) -> Result<Index, String> {
+        let mut file = File::open(file_name).map_err(|e| e.to_string())?;
+        let (size, mtime) = stamp(&file).map_err(|e| e.to_string())?;
+        let mut state = formats.input_state(file.metadata().and_then(|m| m.modified()).ok());
+        let mut checkpoints: Vec<Checkpoint> = Vec::new();
+        // start of the line being read, the part read so far
+        let mut line_start = 0;
+        let mut line = Vec::new();
+
+        // gzip header, raw deflate data behind it
+        let mut inflate = Inflate::new(47)?;
+        let mut input = vec![0; CHUNK];
+        let mut window = vec![0; WINDOW];
+        let mut total_in: u64 = 0;
+        let mut total_out: u64 = 0;
+        let mut last: u64 = 0;
+        loop {
+            if inflate.strm.avail_in == 0 {
+                let len = file.read(&mut input).map_err(|e| e.to_string())?;
+                if len == 0 {
+                    return Err("unexpected end of file".to_string());
+                }
+                inflate.strm.next_in = input.as_mut_ptr();
+                inflate.strm.avail_in = len as u32;
+            }
+            if inflate.strm.avail_out == 0 {
+                inflate.strm.next_out = window.as_mut_ptr();
+                inflate.strm.avail_out = WINDOW as u32;
+            }
+            // the window is the output buffer, it is filled round robin
+            let (avail_in, avail_out) = (inflate.strm.avail_in, inflate.strm.avail_out);
+            let ret = inflate.inflate(z::Z_BLOCK)?;
+            total_in += (avail_in - inflate.strm.avail_in) as u64;
+            total_out += (avail_out - inflate.strm.avail_out) as u64;
+
+            for b in &window[WINDOW - avail_out as usize..WINDOW - inflate.strm.avail_out as usize] {
+                line.push(*b);
+                if *b == b'\n' {
+                    if let Some(checkpoint) = checkpoints.last_mut() {
+                        if checkpoint.first.is_none() && (line_start > checkpoint.output || checkpoint.output == 0) {
+                            checkpoint.first = formats.normalize_in(&line, &mut state).map(|value| value.date_value);
+                        }
+                    }
+                    line_start += line.len() as u64;
+                    line.clear();
+                }
+            }
+
+            if ret == z::Z_STREAM_END {
+                // next member, if any
+                if inflate.strm.avail_in == 0 {
+                    let len = file.read(&mut input).map_err(|e| e.to_string())?;
+                    if len == 0 {
+                        break;
+                    }
+                    inflate.strm.next_in = input.as_mut_ptr();
+                    inflate.strm.avail_in = len as u32;
+                }
+                inflate.reset()?;
+                continue;
+            }
+            // end of a block header, not of the last block
+            let data_type = inflate.strm.data_type;
+            if (data_type & 128 != 0) && (data_type & 64 == 0) && (total_out == 0 || total_out - last > span) {
+                let left = inflate.strm.avail_out as usize;
+                let mut dictionary = Vec::with_capacity(WINDOW);
+                dictionary.extend_from_slice(&window[WINDOW - left..]);
+                dictionary.extend_from_slice(&window[..WINDOW - left]);
+                checkpoints.push(Checkpoint {
+                    input: total_in,
+                    bits: (data_type & 7) as u8,
+                    output: total_out,
+                    first: None,
+                    window: dictionary,
+                });
+                last = total_out;
+            }
+        }
+        Ok(Index {
+            size,
+            mtime,
+            settings: formats.settings(),
+            checkpoints,
+        })
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        for value in [self.size, self.mtime, self.settings.len() as u64] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.write_all(self.settings.as_bytes())?;
+        out.write_all(&(self.checkpoints.len() as u64).to_le_bytes())?;
+        for checkpoint in &self.checkpoints {
+            out.write_all(&checkpoint.input.to_le_bytes())?;
+            out.write_all(&[checkpoint.bits, checkpoint.first.is_some() as u8])?;
+            out.write_all(&checkpoint.output.to_le_bytes())?;
+            out.write_all(&checkpoint.first.unwrap_or(0).to_le_bytes())?;
+            let mut window = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            window.write_all(&checkpoint.window)?;
+            let window = window.finish()?;
+            out.write_all(&(window.len() as u32).to_le_bytes())?;
+            out.write_all(&window)?;
+        }
+        out.flush()
+    }
+
+    pub fn read(path: &str) -> std::io::Result<Index> {
+        let mut input = std::io::BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "not an index"));
+        }
+        let (size, mtime, len) = (read_u64(&mut input)?, read_u64(&mut input)?, read_u64(&mut input)?);
+        let mut settings = Vec::new();
+        (&mut input).take(len).read_to_end(&mut settings)?;
+        let settings = String::from_utf8_lossy(&settings).into_owned();
+        let count = read_u64(&mut input)?;
+        let mut checkpoints = Vec::new();
+        for _ in 0..count {
+            let input_offset = read_u64(&mut input)?;
+            let mut flags = [0; 2];
+            input.read_exact(&mut flags)?;
+            let output = read_u64(&mut input)?;
+            let first = read_u64(&mut input)?;
+            let mut len = [0; 4];
+            input.read_exact(&mut len)?;
+            let mut window = Vec::with_capacity(WINDOW);
+            DeflateDecoder::new((&mut input).take(u32::from_le_bytes(len) as u64)).read_to_end(&mut window)?;
+            checkpoints.push(Checkpoint {
+                input: input_offset,
+                bits: flags[0],
+                output,
+                first: (flags[1] != 0).then_some(first),
+                window,
+            });
+        }
+        Ok(Index {
+            size,
+            mtime,
+            settings,
+            checkpoints,
+        })
+    }
+
+    /// Last checkpoint with a line before `start`, None if there's no such one
+    /// or the timestamps aren't sorted
+    pub fn checkpoint(&self, start: u64) -> Option<&Checkpoint> {
+        let firsts: Vec<u64> = self.checkpoints.iter().filter_map(|checkpoint| checkpoint.first).collect();
+        if firsts.windows(2).any(|pair| pair[0] > pair[1]) {
+            return None;
+        }
+        self.checkpoints
+            .iter()
+            .filter(|checkpoint| checkpoint.output > 0)
+            .take_while(|checkpoint| checkpoint.first.is_none_or(|first| first < start))
+            .filter(|checkpoint| checkpoint.first.is_some())
+            .last()
+    }
+}
+
+/// Checkpoint of the current sidecar of the gzip file to resume before `start`, the file is
+/// positioned there. None without sidecar, if it's outdated, was normalized with other settings
+/// than `formats` or has no checkpoint before start.
+pub fn checkpoint(file: &mut File, file_name: &str, start: u64, formats: &FormatRegistry, debug: u8) -> Option<Checkpoint> {
+    if start == 0 {
+        return None;
+    }
+    let sidecar = Index::sidecar(file_name);
+    let index = Index::read(&sidecar).ok()?;
+    if stamp(file).ok()? != (index.size, index.mtime) {
+        if debug > 0 {
+            eprintln!("{}: outdated, not used", sidecar);
+        }
+        return None;
+    }
+    // timestamps in another zone than the start date
+    if index.settings != formats.settings() {
+        if debug > 0 {
+            eprintln!("{}: built with other time zone or year options, not used", sidecar);
+        }
+        return None;
+    }
+    let checkpoint = index.checkpoint(start)?.clone();
+    file.seek(SeekFrom::Start(checkpoint.input - (checkpoint.bits > 0) as u64)).ok()?;
+    if debug > 0 {
+        eprintln!("{}: index checkpoint at byte {} ({} uncompressed)", file_name, checkpoint.input, checkpoint.output);
+    }
+    Some(checkpoint)
+}
+
+// size and mtime in seconds
+fn stamp(file: &File) -> std::io::Result<(u64, u64)> {
+    let metadata = file.metadata()?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).map_or(0, |mtime| mtime.as_secs());
+    Ok((metadata.len(), mtime))
+}
+
+fn read_u64(input: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Uncompressed content from a checkpoint on, the input is positioned at the checkpoint.
+/// The first (partial) line is skipped, members after the one of the checkpoint are read too.
+pub struct IndexedReader<R: Read> {
+    stage: Stage<R>,
+    skip_line: bool,
+}
+
+enum Stage<R: Read> {
+    Deflate { inflate: Inflate, input: R, buf: Vec<u8> },
+    Members(MultiGzDecoder<Chain<Cursor<Vec<u8>>, R>>),
+    Done,
+}
+
+impl<R: Read> IndexedReader<R> {
+    pub fn new(mut input: R, checkpoint: &Checkpoint) -> Result<IndexedReader<R>, String> {
+        let mut inflate = Inflate::new(-15)?;
+        if checkpoint.bits > 0 {
+            let mut byte = [0; 1];
+            input.read_exact(&mut byte).map_err(|e| e.to_string())?;
+            // the bits of the block in the byte before
+            inflate.prime(checkpoint.bits, byte[0] >> (8 - checkpoint.bits))?;
+        }
+        inflate.set_dictionary(&checkpoint.window)?;
+        Ok(IndexedReader {
+            stage: Stage::Deflate {
+                inflate,
+                input,
+                buf: vec![0; CHUNK],
+            },
+            skip_line: true,
+        })
+    }
+
+    fn read_stage(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let (inflate, input, buf) = match &mut self.stage {
+                Stage::Deflate { inflate, input, buf } => (inflate, input, buf),
+                Stage::Members(members) => return members.read(out),
+                Stage::Done => return Ok(0),
+            };
+            if inflate.strm.avail_in == 0 {
+                let len = input.read(buf)?;
+                if len == 0 {
+                    return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unexpected end of file"));
+                }
+                inflate.strm.next_in = buf.as_mut_ptr();
+                inflate.strm.avail_in = len as u32;
+            }
+            inflate.strm.next_out = out.as_mut_ptr();
+            inflate.strm.avail_out = out.len() as u32;
+            let ret = inflate.inflate(z::Z_NO_FLUSH).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let len = out.len() - inflate.strm.avail_out as usize;
+            if ret == z::Z_STREAM_END {
+                let consumed = inflate.strm.next_in as usize - buf.as_ptr() as usize;
+                let rest = buf[consumed..consumed + inflate.strm.avail_in as usize].to_vec();
+                self.next_member(rest)?;
+            }
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+
+    // behind the trailer of the member
+    fn next_member(&mut self, mut rest: Vec<u8>) -> std::io::Result<()> {
+        let mut input = match std::mem::replace(&mut self.stage, Stage::Done) {
+            Stage::Deflate { input, .. } => input,
+            _ => return Ok(()),
+        };
+        // crc and size
+        let mut trailer = [0; 8];
+        if rest.len() < trailer.len() {
+            let missing = trailer.len() - rest.len();
+            input.read_exact(&mut trailer[..missing])?;
+            rest.clear();
+        } else {
+            rest.drain(..trailer.len());
+        }
+        if rest.is_empty() {
+            let mut byte = [0; 1];
+            if input.read(&mut byte)? == 0 {
+                return Ok(());
+            }
+            rest.push(byte[0]);
+        }
+        self.stage = Stage::Members(MultiGzDecoder::new(Cursor::new(rest).chain(input)));
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for IndexedReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let len = self.read_stage(out)?;
+            if !self.skip_line || len == 0 {
+                return Ok(len);
+            }
+            if let Some(newline) = out[..len].iter().position(|b| *b == b'\n') {
+                self.skip_line = false;
+                out.copy_within(newline + 1..len, 0);
+                if len > newline + 1 {
+                    return Ok(len - newline - 1);
+                }
+            }
+        }
+    }
+}
+
+// zlib inflate stream, boxed as zlib keeps its address
+struct Inflate {
+    strm: Box<z::z_stream>,
+}
+
+impl Inflate {
+    fn new(window_bits: c_int) -> Result<Inflate, String> {
+        let mut inflate = Inflate {
+            strm: Box::new(z::z_stream {
+                next_in: ptr::null_mut(),
+                avail_in: 0,
+                total_in: 0,
+                next_out: ptr::null_mut(),
+                avail_out: 0,
+                total_out: 0,
+                msg: ptr::null_mut(),
+                state: ptr::null_mut(),
+                zalloc,
+                zfree,
+                opaque: ptr::null_mut(),
+                data_type: 0,
+                adler: 0,
+                reserved: 0,
+            }),
+        };
+        let ret = unsafe {
+            z::inflateInit2_(&mut *inflate.strm, window_bits, z::zlibVersion(), std::mem::size_of::<z::z_stream>() as c_int)
+        };
+        if ret != z::Z_OK {
+            // nothing to end
+            inflate.strm.state = ptr::null_mut();
+            return Err(format!("zlib init failed ({ret})"));
+        }
+        Ok(inflate)
+    }
+
+    fn inflate(&mut self, flush: c_int) -> Result<c_int, String> {
+        let ret = unsafe { z::inflate(&mut *self.strm, flush) };
+        match ret {
+            z::Z_OK | z::Z_STREAM_END | z::Z_BUF_ERROR => Ok(ret),
+            z::Z_NEED_DICT => Err("invalid deflate data, needs a dictionary".to_string()),
+            _ => self.check(ret).map(|_| ret),
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), String> {
+        let ret = unsafe { z::inflateReset(&mut *self.strm) };
+        self.check(ret)
+    }
+
+    fn prime(&mut self, bits: u8, value: u8) -> Result<(), String> {
+        let ret = unsafe { z::inflatePrime(&mut *self.strm, bits as c_int, value as c_int) };
+        self.check(ret)
+    }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<(), String> {
+        let ret = unsafe { z::inflateSetDictionary(&mut *self.strm, dictionary.as_ptr(), dictionary.len() as z::uInt) };
+        self.check(ret)
+    }
+
+    fn check(&self, ret: c_int) -> Result<(), String> {
+        if ret == z::Z_OK {
+            return Ok(());
+        }
+        let message = if self.strm.msg.is_null() {
+            format!("zlib error {ret}")
+        } else {
+            unsafe { std::ffi::CStr::from_ptr(self.strm.msg) }.to_string_lossy().into_owned()
+        };
+        Err(message)
+    }
+}
+
+impl Drop for Inflate {
+    fn drop(&mut self) {
+        if !self.strm.state.is_null() {
+            unsafe { z::inflateEnd(&mut *self.strm) };
+        }
+    }
+}
+
+// zlib allocates through the global allocator, the size is kept in front of the block
+const ALIGN: usize = 16;
+
+unsafe extern "C" fn zalloc(_opaque: *mut c_void, items: z::uInt, size: z::uInt) -> *mut c_void {
+    let size = match (items as usize).checked_mul(size as usize).and_then(|size| size.checked_add(ALIGN)) {
+        Some(size) => size,
+        None => return ptr::null_mut(),
+    };
+    let layout = match Layout::from_size_align(size, ALIGN) {
+        Ok(layout) => layout,
+        Err(_) => return ptr::null_mut(),
+    };
+    let block = alloc::alloc(layout);
+    if block.is_null() {
+        return ptr::null_mut();
+    }
+    *(block as *mut usize) = size;
+    block.add(ALIGN) as *mut c_void
+}
+
+unsafe extern "C" fn zfree(_opaque: *mut c_void, address: *mut c_void) {
+    if address.is_null() {
+        return;
+    }
+    let block = (address as *mut u8).sub(ALIGN);
+    let size = *(block as *mut usize);
+    alloc::dealloc(block, Layout::from_size_align_unchecked(size, ALIGN));
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::format::FormatRegistry;
+use crate::rotate;
+
+// output of a file kept in memory, more goes to a temp file
+const SPILL: usize = 1_048_576;
+
+/// Order of the files in the output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileOrder {
+    /// as given
+    Input,
+    /// by their first timestamp, files without one (archives) at the end
+    Time,
+}
+
+impl FromStr for FileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FileOrder, String> {
+        match s {
+            "input" => Ok(FileOrder::Input),
+            "time" => Ok(FileOrder::Time),
+            _ => Err(format!("invalid order: {s} (input or time)")),
+        }
+    }
+}
+
+impl FileOrder {
+    /// The files in this order, the lines of overlapping files aren't interleaved
+    pub fn sort(&self, files: Vec<String>, formats: &FormatRegistry) -> Vec<String> {
+        match self {
+            FileOrder::Input => files,
+            FileOrder::Time => {
+                let mut files: Vec<_> = files.into_iter().map(|file| (rotate::first_timestamp(&file, formats), file)).collect();
+                files.sort_by_key(|(first, _)| (first.is_none(), *first));
+                files.into_iter().map(|(_, file)| file).collect()
+            }
+        }
+    }
+}
+
+/// Output of one file filtered by a job, written once the files before it are written
+#[derive(Default)]
+pub struct Spool {
+    memory: Vec<u8>,
+    file: Option<File>,
+}
+
+impl Spool {
+    pub fn new() -> Spool {
+        Spool::default()
+    }
+
+    /// Whether the output went to a temp file
+    pub fn spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Writes the output, the temp file is removed afterwards
+    pub fn copy_to(self, output: &mut impl Write) -> io::Result<()> {
+        output.write_all(&self.memory)?;
+        if let Some(mut file) = self.file {
+            file.seek(SeekFrom::Start(0))?;
+            io::copy(&mut file, output)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for Spool {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() && self.memory.len() + buf.len() > SPILL {
+            // unnamed, it's gone with the handle
+            let mut file = tempfile::tempfile()?;
+            file.write_all(&self.memory)?;
+            self.memory = Vec::new();
+            self.file = Some(file);
+        }
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => {
+                self.memory.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().map_or(Ok(()), |file| file.flush())
+    }
+}
+
+/// Filters the files on `jobs` threads. `filter` writes the output of a file to its spool and
+/// tells whether the file was corrupt, `write` gets the spools in the order of the files.
+pub fn in_order(
+    files: &[String],
+    jobs: usize,
+    filter: impl Fn(&str, &mut Spool) -> bool + Sync,
+    mut write: impl FnMut(&str, Spool, bool),
+) {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            let (next, filter, sender) = (&next, &filter, sender.clone());
+            scope.spawn(move || {
+                let mut idx = next.fetch_add(1, Ordering::Relaxed);
+                while let Some(file) = files.get(idx) {
+                    let mut spool = Spool::new();
+                    let corrupt = filter(file, &mut spool);
+                    if sender.send((idx, spool, corrupt)).is_err() {
+                        break;
+                    }
+                    idx = next.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+        drop(sender);
+        // files done before those in front of them wait here
+        let mut done = BTreeMap::new();
+        let mut written = 0;
+        for (idx, spool, corrupt) in receiver {
+            done.insert(idx, (spool, corrupt));
+            while let Some((spool, corrupt)) = done.remove(&written) {
+                write(&files[written], spool, corrupt);
+                written += 1;
+            }
+        }
+    });
+}
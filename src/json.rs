@@ -0,0 +1,210 @@
+use std::io::Write;
+use std::ops::Range;
+
+use crate::format::{Epoch, FormatRegistry, InputState, LogFormat};
+use crate::write_to_output;
+
+/// Fields tried when none are configured
+pub const DEFAULT_FIELDS: [&str; 3] = ["@timestamp", "ts", "time"];
+
+/// JSON Lines: one object per line, the timestamp is the value of a field.
+/// A path like "kubernetes.ts" looks into nested objects. Strings are parsed
+/// with the builtin formats, numbers as epoch. The line is scanned, not deserialized.
+pub struct Json {
+    fields: Vec<String>,
+    inner: FormatRegistry,
+}
+
+impl Json {
+    /// `fast` selects the byte parsers for the values, like `FormatRegistry::builtin`
+    pub fn new(fields: Vec<String>, fast: bool) -> Json {
+        let fields = if fields.is_empty() {
+            DEFAULT_FIELDS.iter().map(|field| field.to_string()).collect()
+        } else {
+            fields
+        };
+        let mut inner = FormatRegistry::builtin(fast);
+        inner.register(Box::new(Epoch));
+        Json { fields, inner }
+    }
+
+    // span of the value of the first configured field
+    #[inline(always)]
+    fn value(&self, buf: &[u8]) -> Option<Range<usize>> {
+        let idx = skip_whitespace(buf, 0);
+        if buf.get(idx) != Some(&b'{') {
+            return None;
+        }
+        self.fields.iter().find_map(|field| lookup(buf, idx, field.as_bytes()))
+    }
+
+    // the timestamp within the value: contents of a string or the number
+    fn timestamp(&self, buf: &[u8]) -> Option<Range<usize>> {
+        let value = self.value(buf)?;
+        if buf[value.start] == b'"' {
+            Some(value.start + 1..value.end - 1)
+        } else {
+            Some(value)
+        }
+    }
+
+    // the inner format which parses the timestamp
+    fn format(&self, timestamp: &[u8]) -> Option<&dyn LogFormat> {
+        self.inner
+            .formats()
+            .iter()
+            .find(|format| format.detect(timestamp) && format.parse(timestamp).is_some())
+            .map(|format| format.as_ref())
+    }
+}
+
+impl LogFormat for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        self.value(buf).is_some()
+    }
+
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        self.parse_with(buf, &mut InputState::default())
+    }
+
+    fn parse_with(&self, buf: &[u8], state: &mut InputState) -> Option<u64> {
+        let timestamp = &buf[self.timestamp(buf)?];
+        self.format(timestamp)?.parse_with(timestamp, state)
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        match self.timestamp(buf) {
+            Some(timestamp) => {
+                let span = match self.format(&buf[timestamp.clone()]) {
+                    Some(format) => format.prefix_span(&buf[timestamp.clone()]),
+                    None => 0..timestamp.len(),
+                };
+                timestamp.start + span.start..timestamp.start + span.end
+            }
+            None => 0..0,
+        }
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        output.write_all(b"\"")?;
+        write_to_output(output, date_value)?;
+        output.write_all(b"\"")
+    }
+
+    fn utc_offset(&self, buf: &[u8]) -> Option<i32> {
+        let timestamp = &buf[self.timestamp(buf)?];
+        self.format(timestamp)?.utc_offset(timestamp)
+    }
+
+    fn quote(&self, buf: &[u8]) -> bool {
+        self.value(buf).is_some_and(|value| buf[value.start] != b'"')
+    }
+
+    /// Top level field, strings without the quotes
+    fn field<'a>(&self, buf: &'a [u8], name: &str) -> Option<&'a [u8]> {
+        let value = lookup(buf, skip_whitespace(buf, 0), name.as_bytes())?;
+        if buf[value.start] == b'"' {
+            Some(&buf[value.start + 1..value.end - 1])
+        } else {
+            Some(&buf[value])
+        }
+    }
+}
+
+// value of `path` in the object at idx, a dot goes into a nested object
+// unless the object has a key with the dot
+fn lookup(buf: &[u8], idx: usize, path: &[u8]) -> Option<Range<usize>> {
+    if let Some(value) = member(buf, idx, path) {
+        return Some(value);
+    }
+    path.iter().enumerate().filter(|(_, b)| **b == b'.').find_map(|(dot, _)| {
+        let value = member(buf, idx, &path[..dot])?;
+        lookup(buf, value.start, &path[dot + 1..])
+    })
+}
+
+// value of the key in the object at idx
+fn member(buf: &[u8], idx: usize, key: &[u8]) -> Option<Range<usize>> {
+    if buf.get(idx) != Some(&b'{') {
+        return None;
+    }
+    let mut idx = idx + 1;
+    loop {
+        idx = skip_whitespace(buf, idx);
+        if *buf.get(idx)? != b'"' {
+            return None;
+        }
+        let key_end = string_end(buf, idx)?;
+        let name = &buf[idx + 1..key_end - 1];
+        idx = skip_whitespace(buf, key_end);
+        if *buf.get(idx)? != b':' {
+            return None;
+        }
+        let start = skip_whitespace(buf, idx + 1);
+        let end = value_end(buf, start)?;
+        if name == key {
+            return Some(start..end);
+        }
+        idx = skip_whitespace(buf, end);
+        if *buf.get(idx)? != b',' {
+            return None;
+        }
+        idx += 1;
+    }
+}
+
+// index behind the value at idx
+fn value_end(buf: &[u8], idx: usize) -> Option<usize> {
+    match *buf.get(idx)? {
+        b'"' => string_end(buf, idx),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut idx = idx;
+            loop {
+                match *buf.get(idx)? {
+                    b'"' => {
+                        idx = string_end(buf, idx)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(idx + 1);
+                        }
+                    }
+                    _ => (),
+                }
+                idx += 1;
+            }
+        }
+        _ => {
+            let len = buf[idx..]
+                .iter()
+                .take_while(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                .count();
+            (len > 0).then_some(idx + len)
+        }
+    }
+}
+
+// index behind the closing quote of the string at idx
+fn string_end(buf: &[u8], idx: usize) -> Option<usize> {
+    let mut idx = idx + 1;
+    loop {
+        match *buf.get(idx)? {
+            b'\\' => idx += 2,
+            b'"' => return Some(idx + 1),
+            _ => idx += 1,
+        }
+    }
+}
+
+#[inline(always)]
+fn skip_whitespace(buf: &[u8], idx: usize) -> usize {
+    idx + buf.get(idx..).map_or(0, |rest| rest.iter().take_while(|b| b.is_ascii_whitespace()).count())
+}
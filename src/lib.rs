@@ -1,18 +1,48 @@
+pub mod archive;
+pub mod clf;
+pub mod decode;
+pub mod epoch;
+pub mod files;
+pub mod follow;
+pub mod format;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod jobs;
+pub mod json;
+pub mod locate;
+pub mod rotate;
+pub mod sample;
+pub mod seek;
+pub mod strict;
+pub mod syslog;
 pub mod tests;
+pub mod zone;
 
-use chrono::naive::NaiveDateTime;
-use chrono::{Datelike, Timelike};
+use chrono::naive::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, FixedOffset, Timelike};
 use colored::Colorize;
-use flate2::read;
 use lazy_static::lazy_static;
-use std::ffi::OsStr;
+use globset::GlobSet;
+use memmap2::Mmap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::io::{BufRead, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+use archive::Archive;
+use decode::{Corruption, Decoded};
+use format::{Carmen, CarmenErr, Epoch, Iso, LogFormat, Yoda};
+use sample::Sampler;
+use strict::{Rejection, RejectionReport};
+pub use format::FormatRegistry;
+use zone::{DstPolicy, Zone};
+
+/// First year of the century two digit years fall into: 70..99 are 1970..1999, 00..69 are 2000..2069
+pub const DEFAULT_PIVOT_YEAR: i32 = 1970;
 
 lazy_static! {
-    // it is assumed that the log is written in the current century
-    static ref CENTURY: i32 = (chrono::offset::Local::now().year() / 100) * 100;
+    // carmen, yoda and carmen error parsed by chrono
+    static ref NAIVE_FORMATS: FormatRegistry = FormatRegistry::builtin(false);
 }
 
 pub struct DateTimeHolder {
@@ -22,24 +52,47 @@ pub struct DateTimeHolder {
 
 impl DateTimeHolder {
     pub fn new(start: Option<&String>, end: Option<&String>) -> DateTimeHolder {
-        let start = match normalized_command_line_date(start) {
+        DateTimeHolder::with_zone(start, end, None, DstPolicy::Auto)
+    }
+
+    /// Start and end date given in `zone`, normalized to UTC.
+    /// In doubt the window gets wider (DstPolicy::Auto).
+    pub fn with_zone(start: Option<&String>, end: Option<&String>, zone: Option<&Zone>, dst: DstPolicy) -> DateTimeHolder {
+        DateTimeHolder::with_options(start, end, zone, dst, DEFAULT_PIVOT_YEAR)
+    }
+
+    /// Like `with_zone`, two digit years fall into the century starting at `pivot`
+    pub fn with_options(start: Option<&String>, end: Option<&String>, zone: Option<&Zone>, dst: DstPolicy, pivot: i32) -> DateTimeHolder {
+        let (start, start_offset) = match normalized_command_line_date(start, false, pivot) {
             Some(Ok(value)) => value,
             Some(Err(e)) => {
                 eprintln!("Start-Date: {}", e.bold().red());
                 ::std::process::exit(1);
             }
-            None => 0,
+            None => (0, None),
         };
-        let end = match normalized_command_line_date(end) {
+        let (end, end_offset) = match normalized_command_line_date(end, true, pivot) {
             Some(Ok(value)) => value,
             Some(Err(e)) => {
                 eprintln!("End-Date{}", e.bold().red());
                 ::std::process::exit(1);
             }
-            None => u64::MAX,
+            None => (u64::MAX, None),
         };
 
-        DateTimeHolder { start, end }
+        match zone {
+            Some(zone) => {
+                let (start_dst, end_dst) = match dst {
+                    DstPolicy::Auto => (DstPolicy::Earliest, DstPolicy::Latest),
+                    dst => (dst, dst),
+                };
+                // an offset within the date wins over the zone
+                let start = if start == 0 { 0 } else { zone_bound(zone, start_offset, start, start_dst, "Start-Date") };
+                let end = if end == u64::MAX { u64::MAX } else { zone_bound(zone, end_offset, end, end_dst, "End-Date") };
+                DateTimeHolder { start, end }
+            }
+            None => DateTimeHolder { start, end },
+        }
     }
 
     pub fn validate(&self) -> bool {
@@ -50,244 +103,529 @@ impl DateTimeHolder {
     }
 }
 
-/// Main entry point
+fn zone_bound(zone: &Zone, offset: Option<i32>, value: u64, dst: DstPolicy, name: &str) -> u64 {
+    let utc = match offset.and_then(FixedOffset::east_opt) {
+        Some(offset) => Zone::Fixed(offset).to_utc(value, dst, 0),
+        None => zone.to_utc(value, dst, 0),
+    };
+    match utc {
+        Some(value) => value,
+        None => {
+            eprintln!("{}: {}", name, "ambiguous or missing local time".bold().red());
+            ::std::process::exit(1);
+        }
+    }
+}
+
+/// Main entry point, returns where a corrupt input couldn't be decoded any further
 #[inline(never)]
 pub fn process_file(
     start_end_date: &DateTimeHolder,
     file_name: Option<&str>,
-    debug: u8,
-    fast: bool,
-    replace: bool,
+    formats: &FormatRegistry,
+    options: &Options,
     output: &mut impl Write,
     input: &mut impl Read,
-) {
-    // read from stdin, or file (gzip)
+) -> Option<Corruption> {
+    let debug = options.debug;
+    // modification time of the file, for layouts without year
+    let mut reference = None;
+    let filter = Filter {
+        start_end_date,
+        formats,
+        options,
+        follow: false,
+    };
+    let mut bw = BufWriter::with_capacity(262_144, output);
+    // read from stdin, or file, compressed ones are recognized by their content
     #[allow(clippy::unnecessary_unwrap)]
-    let mut buf_reader: Box<dyn BufRead> = if file_name.is_none() {
-        Box::new(BufReader::with_capacity(262_144, input))
+    let decoded = if file_name.is_none() {
+        decode::reader(input)
     } else {
         let file_name = file_name.unwrap();
         let file = File::open(file_name);
-        if let Ok(file) = file {
-            let path = Path::new(file_name);
-            if path.extension() == Some(OsStr::new("gz")) {
-                Box::new(BufReader::with_capacity(
-                    262_144,
-                    read::GzDecoder::new(file),
-                ))
-            } else {
-                Box::new(BufReader::with_capacity(262_144, file))
+        if let Ok(mut file) = file {
+            reference = file.metadata().and_then(|m| m.modified()).ok();
+            if let Some(map) = map_plain(&file, options) {
+                if debug > 0 {
+                    eprintln!("{}: decoder none, mapped {} bytes", file_name, map.len());
+                }
+                let offset = seek_start(&mut file, start_end_date, formats, options, reference).unwrap_or(0);
+                if debug > 0 && offset > 0 {
+                    eprintln!("{}: seek to byte {}", file_name, offset);
+                }
+                let content = &map[offset as usize..];
+                return filter.lines(file_name, false, reference, Input::Mapped(content), &mut bw);
             }
+            open_at(file, file_name, start_end_date, formats, options, reference)
         } else {
             eprintln!("Could not open file {}", file_name.bold().red());
-            return;
+            return None;
         }
     };
-
-    let mut buf: Vec<u8> = Vec::with_capacity(4096);
-    let mut bw = BufWriter::with_capacity(262_144, output);
-    while let Ok(bytes_read) = buf_reader.read_until(0x0A_u8, &mut buf) {
-        if bytes_read == 0 {
-            break;
+    let name = file_name.unwrap_or("stdin");
+    let mut decoded = match decoded {
+        Ok(decoded) => {
+            if debug > 0 {
+                eprintln!("{}: decoder {}", name, decoded.compression.name());
+            }
+            decoded
+        }
+        Err(e) => {
+            eprintln!("{}: {}", name.bold(), e.red());
+            return None;
         }
+    };
 
-        if buf.len() < 19 {
-            if debug > 1 {
-                eprintln!("{}{}", "Line to short: ".bright_red(), unsafe {
-                    std::str::from_utf8_unchecked(&buf)
-                });
+    let archive = Archive::sniff(decoded.reader.fill_buf().unwrap_or_default());
+    match archive {
+        None => filter.lines(name, false, reference, Input::Buffered(&mut decoded), &mut bw),
+        Some(archive) => {
+            if debug > 0 {
+                eprintln!("{}: archive {}", name, archive.name());
             }
-            buf.clear();
-            continue;
+            // the first corrupt member, the ones behind it are read anyway
+            let mut corruption = None;
+            // members are decoded on their own, nested .gz files too
+            let members = archive.members(&mut decoded.reader, options.members.as_ref(), |member, mtime, content| {
+                let label = format!("{name}!{member}");
+                match decode::reader(content) {
+                    Ok(mut member) => {
+                        if debug > 0 {
+                            eprintln!("{}: decoder {}", label, member.compression.name());
+                        }
+                        let member = filter.lines(&label, true, mtime.or(reference), Input::Buffered(&mut member), &mut bw);
+                        corruption = corruption.take().or(member);
+                    }
+                    Err(e) => eprintln!("{}: {}", label.bold(), e.red()),
+                }
+            });
+            if let Err(error) = members {
+                // the archive structure, members read before are processed
+                let corruption = Corruption {
+                    decoded: 0,
+                    consumed: decoded.consumed(),
+                    error,
+                };
+                eprintln!(
+                    "{}: {} ({} bytes read): {}",
+                    name.bold(),
+                    "corrupt archive".bold().red(),
+                    corruption.consumed,
+                    corruption.error
+                );
+                return Some(corruption);
+            }
+            corruption
         }
+    }
+}
+
+// the content of an uncompressed regular file, None for inputs read through a buffer:
+// not asked for, empty, compressed or an archive
+fn map_plain(file: &File, options: &Options) -> Option<Mmap> {
+    if !options.mmap || !file.metadata().is_ok_and(|m| m.is_file() && m.len() > 0) {
+        return None;
+    }
+    // a file truncated while it's mapped ends the process (SIGBUS), hence only with --mmap
+    let map = unsafe { Mmap::map(file) }.ok()?;
+    let head = &map[..map.len().min(512)];
+    if decode::Compression::sniff(head) != decode::Compression::None || Archive::sniff(head).is_some() {
+        return None;
+    }
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Some(map)
+}
+
+// the file from the start, or from near the start date with --seek or a gzip index
+fn open_at<'a>(
+    mut file: File,
+    file_name: &str,
+    start_end_date: &DateTimeHolder,
+    formats: &FormatRegistry,
+    options: &Options,
+    reference: Option<SystemTime>,
+) -> Result<Decoded<'a>, String> {
+    let debug = options.debug;
+    if let Some(offset) = seek_start(&mut file, start_end_date, formats, options, reference) {
+        if debug > 0 {
+            eprintln!("{}: seek to byte {}", file_name, offset);
+        }
+        return Ok(decode::plain(file));
+    }
+    #[cfg(feature = "index")]
+    if let Some(checkpoint) = index::checkpoint(&mut file, file_name, start_end_date.start, formats, debug) {
+        return decode::decoded_by(file, decode::Compression::Gzip, |input| index::IndexedReader::new(input, &checkpoint));
+    }
+    decode::reader(file)
+}
+
+// offset of the file positioned near the start date, None if the file is read from the start:
+// not asked for, no start date, not a regular uncompressed file or not sorted
+fn seek_start(file: &mut File, start_end_date: &DateTimeHolder, formats: &FormatRegistry, options: &Options, reference: Option<SystemTime>) -> Option<u64> {
+    if !options.seek || start_end_date.start == 0 || !file.metadata().is_ok_and(|m| m.is_file()) {
+        return None;
+    }
+    let mut head = Vec::new();
+    let plain = (&mut *file).take(512).read_to_end(&mut head).is_ok()
+        && decode::Compression::sniff(&head) == decode::Compression::None
+        && Archive::sniff(&head).is_none();
+    let offset = if plain { seek::start_offset(file, start_end_date.start, formats, reference).ok() } else { None };
+    file.seek(SeekFrom::Start(offset.unwrap_or(0))).ok()?;
+    offset.filter(|offset| *offset > 0)
+}
+
+/// Like `process_file` for a file which is still written, like tail -F. It ends after a line
+/// behind the end date, or after `idle` without new lines.
+pub fn follow_file(
+    start_end_date: &DateTimeHolder,
+    file_name: &str,
+    formats: &FormatRegistry,
+    options: &Options,
+    output: &mut impl Write,
+    idle: Option<Duration>,
+) -> Option<Corruption> {
+    let debug = options.debug;
+    let mut file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(_) => {
+            eprintln!("Could not open file {}", file_name.bold().red());
+            return None;
+        }
+    };
+    let reference = file.metadata().and_then(|m| m.modified()).ok();
+    if let Some(offset) = seek_start(&mut file, start_end_date, formats, options, reference) {
+        if debug > 0 {
+            eprintln!("{}: seek to byte {}", file_name, offset);
+        }
+    }
+    let mut decoded = match follow::Tail::new(file_name, file, idle, debug) {
+        Ok(tail) => decode::plain(tail),
+        Err(e) => {
+            eprintln!("{}: {}", file_name.bold(), e.to_string().red());
+            return None;
+        }
+    };
+    let filter = Filter {
+        start_end_date,
+        formats,
+        options,
+        follow: true,
+    };
+    filter.lines(file_name, false, reference, Input::Buffered(&mut decoded), &mut BufWriter::new(output))
+}
+
+// lines of an input: read through a buffer, or in place from a mapped file
+enum Input<'a, 'b> {
+    Buffered(&'a mut Decoded<'b>),
+    Mapped(&'a [u8]),
+}
+
+/// How the inputs of a run are read and their lines written, besides the dates and formats
+#[derive(Default)]
+pub struct Options {
+    /// debug output, 2 reports every line which isn't parsed
+    pub debug: u8,
+    /// timestamps are written as yyyy-mm-dd hh:mm:ss
+    pub replace: bool,
+    /// only real calendar dates, no hour 24, day 0, month 0 or 31.02., rejected lines are reported
+    pub strict: bool,
+    /// lines without timestamp belong to the record of the line before, up to
+    /// this many lines per record (stack traces). 0 drops them
+    pub record_lines: usize,
+    /// only archive members with a matching name are read
+    pub members: Option<GlobSet>,
+    /// sorted plain files are bisected for the start date instead of read from the first line
+    pub seek: bool,
+    /// uncompressed regular files are mapped into memory and their lines scanned in place
+    pub mmap: bool,
+}
 
-        //let log_datetime = normalized_datetime_naive(&buf);
-        let log_datetime = if fast {normalized_datetime(&buf)} else {normalized_datetime_naive(&buf)};
-        if let Some(log_datetime) = log_datetime {
-            if (log_datetime.date_value >= start_end_date.start) & (log_datetime.date_value <= start_end_date.end) {
-                // BufWriter.write_all() gives UTF-8 errors on windows
-                // let retval = output.write_all(&buf);
-                let mut offset:usize = 0;
-                if replace && log_datetime.log_type != LogType::Yoda(19) {
-                    offset = write_to_output(&mut bw, &log_datetime);
-                    
+// an input filtered with the options of the run
+struct Filter<'a> {
+    start_end_date: &'a DateTimeHolder,
+    formats: &'a FormatRegistry,
+    options: &'a Options,
+    // lines are flushed as they come, the input ends behind the end date
+    follow: bool,
+}
+
+impl Filter<'_> {
+    // filters one input, `labeled` puts the name in front of the lines (archive members)
+    fn lines(
+        &self,
+        name: &str,
+        labeled: bool,
+        reference: Option<SystemTime>,
+        mut input: Input,
+        bw: &mut impl Write,
+    ) -> Option<Corruption> {
+        let Filter {
+            start_end_date,
+            formats,
+            options,
+            follow,
+        } = *self;
+        let Options {
+            debug,
+            replace,
+            strict,
+            record_lines: max_record_lines,
+            ..
+        } = *options;
+        let label = format!("{name}:");
+        let label: &[u8] = if labeled { label.as_bytes() } else { b"" };
+        let mut read_buf: Vec<u8> = Vec::with_capacity(4096);
+        let mut state = formats.input_state(reference);
+        state.spans = replace;
+        state.strict = strict;
+        let mut sampler = Sampler::new(formats, name, debug);
+        let mut report = RejectionReport::new();
+        let mut line = 0;
+        // the current record is emitted, and its number of lines
+        let mut record: Option<bool> = None;
+        let mut record_lines = 0;
+        let mut total: u64 = 0;
+        let mut corruption = None;
+        loop {
+            let buf: &[u8] = match &mut input {
+                Input::Buffered(decoded) => {
+                    read_buf.clear();
+                    match decoded.reader.read_until(0x0A_u8, &mut read_buf) {
+                        Ok(0) => break,
+                        Ok(bytes_read) => total += bytes_read as u64,
+                        Err(e) => {
+                            // the part of the line before the error is still processed
+                            total += read_buf.len() as u64;
+                            corruption = Some(Corruption {
+                                decoded: total,
+                                consumed: decoded.consumed(),
+                                error: e.to_string(),
+                            });
+                            if read_buf.is_empty() {
+                                break;
+                            }
+                            read_buf.push(0x0A_u8);
+                        }
+                    }
+                    &read_buf
+                }
+                Input::Mapped(rest) => {
+                    let content: &[u8] = rest;
+                    if content.is_empty() {
+                        break;
+                    }
+                    let end = memchr::memchr(0x0A_u8, content).map_or(content.len(), |end| end + 1);
+                    *rest = &content[end..];
+                    &content[..end]
+                }
+            };
+            line += 1;
+
+            let log_datetime = if buf.len() < MIN_LINE_LEN { None } else { sampler.normalize(buf, &mut state) };
+            if let Some(log_datetime) = log_datetime {
+                if follow && log_datetime.date_value > start_end_date.end {
+                    break;
+                }
+                let emit = (log_datetime.date_value >= start_end_date.start) & (log_datetime.date_value <= start_end_date.end);
+                record = Some(emit);
+                record_lines = 1;
+                if emit {
+                    // BufWriter.write_all() gives UTF-8 errors on windows
+                    // let retval = output.write_all(&buf);
+                    let retval = if replace {
+                        let span = log_datetime.span;
+                        // the fraction is rendered, unless the line still carries it behind the timestamp
+                        let fraction = (log_datetime.date_value & MICROS_MASK != 0) && !format::has_fraction(buf, span.end);
+                        let quote: &[u8] = if log_datetime.quote { b"\"" } else { b"" };
+                        bw.write_all(label)
+                            .and_then(|_| bw.write_all(&buf[..span.start]))
+                            .and_then(|_| bw.write_all(quote))
+                            .and_then(|_| write_to_output(bw, log_datetime.date_value))
+                            .and_then(|_| if fraction { write_fraction(bw, log_datetime.date_value, b'.') } else { Ok(()) })
+                            .and_then(|_| bw.write_all(quote))
+                            .and_then(|_| bw.write_all(&buf[span.end..]))
+                    } else {
+                        bw.write_all(label).and_then(|_| bw.write_all(buf))
+                    };
+                    write_or_exit(retval);
                 }
-                let retval = bw.write_all(&buf[offset..]);
-                match retval {
-                    Ok(_) => (),
-                    Err(err) => {
-                        eprintln!("{:?}", err);
-                        ::std::process::exit(1);
+            } else if record.is_some() && record_lines < max_record_lines {
+                // continuation of the record before
+                record_lines += 1;
+                if record == Some(true) {
+                    write_or_exit(bw.write_all(label).and_then(|_| bw.write_all(buf)));
+                }
+                if strict {
+                    let reason = formats.rejection(buf);
+                    if reason != Rejection::NoTimestamp {
+                        report.add(reason, line);
+                    }
+                }
+            } else {
+                if record.is_some() && record_lines == max_record_lines && record_lines > 0 {
+                    record_lines += 1;
+                    if debug > 0 {
+                        eprintln!("{}{}", "Record too long, dropping from line ".bright_red(), line);
                     }
                 }
+                if strict {
+                    let reason = if buf.len() < MIN_LINE_LEN { Rejection::NoTimestamp } else { formats.rejection(buf) };
+                    report.add(reason, line);
+                }
+                if debug > 1 {
+                    let message = if buf.len() < MIN_LINE_LEN { "Line to short: " } else { "couldn't parse DateTime: " };
+                    eprintln!("{}{}", message.bright_red(), unsafe {
+                        std::str::from_utf8_unchecked(buf)
+                    });
+                }
+            }
+            if corruption.is_some() {
+                break;
+            }
+            if follow {
+                write_or_exit(bw.flush());
             }
-        } else if debug > 1 {
-                eprintln!("{}{}", "couldn't parse DateTime: ".bright_red(), unsafe {
-                    std::str::from_utf8_unchecked(&buf)
-                });
         }
-        buf.clear();
+        sampler.finish();
+        if strict {
+            report.print(name);
+        }
+        if let Some(corruption) = &corruption {
+            eprintln!(
+                "{}: {} after {} bytes ({} bytes read): {}",
+                name.bold(),
+                "corrupt input".bold().red(),
+                corruption.decoded,
+                corruption.consumed,
+                corruption.error
+            );
+        }
+        corruption
+    }
+}
+
+fn write_or_exit(retval: std::io::Result<()>) {
+    match retval {
+        Ok(_) => (),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            ::std::process::exit(1);
+        }
     }
 }
 
+// year, month, day, hour, minute, second, microsecond
+pub(crate) fn unpack(v: u64) -> (u64, u64, u64, u64, u64, u64, u64) {
+    (
+        v >> 46,
+        (v >> 42) & 0xF,
+        (v >> 37) & 0x1F,
+        (v >> 32) & 0x1F,
+        (v >> 26) & 0x3F,
+        (v >> 20) & 0x3F,
+        v & MICROS_MASK,
+    )
+}
+
+pub(crate) fn to_naive(v: u64) -> Option<NaiveDateTime> {
+    let (year, month, day, hour, minute, second, micro) = unpack(v);
+    NaiveDate::from_ymd_opt(year as i32, month as u32, day as u32)?
+        .and_hms_micro_opt(hour as u32, minute as u32, second as u32, micro as u32)
+}
+
 // YYYY-MM-DD hh:mm:ss
 //#[inline(never)]
-fn write_to_output(bw: &mut BufWriter<&mut impl Write>, input: &NormRetValue) -> usize {
+pub(crate) fn write_to_output(bw: &mut dyn Write, date_value: u64) -> std::io::Result<()> {
     let mut chars : [u8; 19] = Default::default();
-    let v = input.date_value;
-    let year = v >> 40;
+    let (year, month, day, hour, minute, second, _) = unpack(date_value);
     chars[0] =  (year / 1000) as u8 + 48;
     chars[1]=  ((year / 100) % 10) as u8 + 48;
     chars[2] =  ((year / 10) % 10) as u8 + 48;
     chars[3] =  (year % 10) as u8 + 48;
     chars[4] = b'-';
-    let month = (v >> 32) & 0xFF;
     chars[5] = (month / 10) as u8 + 48;
     chars[6] = (month - ((month / 10) * 10)) as u8 + 48;
     chars[7] = b'-';
-    let day = (v >> 24) & 0xFF;
     chars[8] = (day / 10) as u8 + 48;
     chars[9] = (day - ((day / 10) * 10)) as u8 + 48;
     chars[10] = b' ';
-    let hour = (v >> 16) & 0xFF;
     chars[11] = (hour / 10) as u8 + 48;
     chars[12] = (hour - ((hour / 10) * 10)) as u8 + 48;
     chars[13] = b':';
-    let minute = (v >> 8) & 0xFF;
     chars[14] = (minute / 10) as u8 + 48;
     chars[15] = (minute - ((minute / 10) * 10)) as u8 + 48;
     chars[16] = b':';
-    let second = v & 0xFF;
     chars[17] = (second / 10) as u8 + 48;
     chars[18] = (second - ((second / 10) * 10)) as u8 + 48;
      
-    let _ignore = bw.write_all(&chars);
-    (match input.log_type {
-        LogType::Carmen(n) => n,
-        LogType::CarmenErr(n) => n,
-        LogType::Yoda(n) => n,
-    }) as usize 
+    bw.write_all(&chars)
 }
 
-#[derive(PartialEq)]
-pub enum LogType {
-    Carmen(u8),
-    CarmenErr(u8),
-    Yoda(u8),
+// ,SSS or ,SSSSSS (if not a multiple of a millisecond)
+pub(crate) fn write_fraction(bw: &mut dyn Write, date_value: u64, separator: u8) -> std::io::Result<()> {
+    let micro = date_value & MICROS_MASK;
+    if micro.is_multiple_of(1000) {
+        write!(bw, "{}{:03}", separator as char, micro / 1000)
+    } else {
+        write!(bw, "{}{:06}", separator as char, micro)
+    }
 }
 
 pub struct NormRetValue  {
     pub date_value: u64,
-    pub log_type: LogType
+    /// position of the timestamp within the line
+    pub span: Range<usize>,
+    /// the replacement needs quotes (JSON number)
+    pub quote: bool,
 }
 
 // disgusting but ~3x faster 
 // 2023-01-24 13:57:31   yoda               19
+// 2023-01-24T13:57:31Z  iso
 // 24.12.22 00:02:05
 // 20230729111238
 //#[inline(always)]
 pub fn normalized_datetime(buf: &[u8]) -> Option<NormRetValue> 
 {    
-    if (buf.len() > 14) && ( 
-       (buf[0] >= 48) & (buf[0] <= 57) & (buf[1] >= 48) & (buf[1] <= 57) &
-       (buf[3] >= 48) & (buf[3] <= 57) & (buf[6] >= 48) & (buf[6] <= 57) &
-       (buf[9] >= 48) & (buf[9] <= 57) & (buf[12] >= 48) & (buf[12] <= 57)) {
-        // quick check passed 
-    } else  {
+    if !format::quick_check(buf) {
         return None;
     }
-
-    let y2 = (((buf[0] as i32) - 48) * 1000) + (((buf[1] as i32) - 48) * 100);
-    if y2 < 0 {
-        return None;
-    }    
-
-    // yoda format
-    if  (buf.len() >= 19) && ((buf[4] == 45) & (buf[7] == 45) & (buf[10] == 32) & (buf[13] == 58) & (buf[16] == 58)) 
-    {
-        let t2 = (buf[2] >= 48) & (buf[2] <= 57);
-        let t5 = (buf[5] >= 48) & (buf[5] <= 57);
-        let t8 = (buf[8] >= 48) & (buf[8] <= 57);
-        let t11 = (buf[11] >= 48) & (buf[11] <= 57);
-        let t14 = (buf[14] >= 48) & (buf[14] <= 57);
-        let t15 = (buf[15] >= 48) & (buf[15] <= 57);
-        let t17 = (buf[17] >= 48) & (buf[17] <= 57);
-        let t18 = (buf[18] >= 48) & (buf[18] <= 57);
-        let yoda_found =t2 & t5 & t8 & t11 & t14 & t15 & t17 & t18;
-        if yoda_found {
-            let y1 = ((buf[2] as i16) - 48) * 10 + ((buf[3] as i16) - 48);
-            let year = (y1 as i32) + y2;
-            let month = ((buf[5] as i16) - 48) * 10 + ((buf[6] as i16)- 48);
-            let day = ((buf[8] as i16) - 48) * 10 + ((buf[9] as i16)- 48);
-            let hour = ((buf[11] as i16) - 48) * 10 + ((buf[12] as i16) - 48);
-            let minute = ((buf[14] as i16) - 48) * 10 + ((buf[15] as i16) - 48);
-            let second = ((buf[17] as i16) - 48) * 10 + ((buf[18] as i16)- 48);
-
-            let date_value = calc_u64(second, minute, hour, day, month, year)?;
-            return Some(NormRetValue{date_value, log_type:LogType::Yoda(19)});
-        }
+    if Yoda.detect(buf) {
+        return Some(NormRetValue{date_value: Yoda.parse(buf)?, span: Yoda.prefix_span(buf), quote: false});
     }
-    
-    // 20230729111238 Carmen-Error
-    if (buf[2] >= 48) & (buf[2] <= 57) & 
-    (buf[4] >= 48) & (buf[4] <= 57) & 
-    (buf[5] >= 48) & (buf[5] <= 57) & 
-    (buf[7] >= 48) & (buf[7] <= 57) & 
-    (buf[8] >= 48) & (buf[8] <= 57) & 
-    (buf[10] >= 48) & (buf[10] <= 57) &
-    (buf[11] >= 48) & (buf[11] <= 57) &
-    (buf[13] >= 48) & (buf[13] <= 57) 
-    {
-        // parse as carmen error Carmen-Error
-        let y1 = ((buf[2] as i16) - 48) * 10 + ((buf[3] as i16) - 48);
-        let year = (y1 as i32) + y2;
-        let month = ((buf[4] as i16) - 48) * 10 + ((buf[5] as i16) - 48);
-        let day = ((buf[6] as i16) - 48) * 10 + ((buf[7] as i16) - 48);
-        let hour = ((buf[8] as i16) - 48) * 10 + ((buf[9] as i16) - 48);
-        let minute = ((buf[10] as i16) - 48) * 10 + ((buf[11] as i16) - 48);
-        let second = ((buf[12] as i16) - 48) * 10 + ((buf[13] as i16) - 48);
-
-        let date_value = calc_u64(second, minute, hour, day, month, year)?;
-        return Some(NormRetValue{date_value,  log_type:LogType::CarmenErr(14)});
+    if Iso.detect(buf) {
+        return Some(NormRetValue{date_value: Iso.parse(buf)?, span: Iso.prefix_span(buf), quote: false});
     }
-    
-    // 24.12.22 00:02:05 carmen normal 
-    if (buf.len() >= 17) && ((buf[2] == 46) & (buf[5] == 46) & (buf[8] == 32) & (buf[11] == 58) & (buf[14] == 58)) 
-    {
-        let t3 = (buf[4] >= 48) & (buf[5] <= 57);
-        let t5 = (buf[7] >= 48) & (buf[7] <= 57);
-        let t7 = (buf[10] >= 48) & (buf[10] <= 57);
-        let t9 = (buf[13] >= 48) & (buf[13] <= 57);
-        let t10 = (buf[15] >= 48) & (buf[15] <= 57);
-        let t11 = (buf[16] >= 48) & (buf[16] <= 57);
-        let carmen_found =  t3 & t5 & t7 & t9 & t10 & t11;
-        
-        if carmen_found {
-            let year  = (((((buf[6] as i16) - 48) * 10) + ((buf[7] as i16) - 48)) as i32) + *CENTURY;
-            let month = ((buf[3] as i16) - 48) * 10 + ((buf[4] as i16) - 48);
-            let day = ((buf[0] as i16) - 48) * 10 + ((buf[1] as i16) - 48);
-            let hour = ((buf[9] as i16) - 48) * 10 + ((buf[10] as i16) - 48);
-            let minute = ((buf[12] as i16) - 48) * 10 + ((buf[13] as i16) - 48);
-            let second = ((buf[15] as i16) - 48) * 10 + ((buf[16] as i16) - 48);
-
-            let date_value = calc_u64(second, minute, hour, day, month, year)?;
-            return Some(NormRetValue{date_value,  log_type:LogType::Carmen(17)});
-        }
+    if CarmenErr.detect(buf) {
+        return Some(NormRetValue{date_value: CarmenErr.parse(buf)?, span: CarmenErr.prefix_span(buf), quote: false});
     }
-
-   None
+    if Carmen.detect(buf) {
+        return Some(NormRetValue{date_value: Carmen.parse(buf)?, span: Carmen.prefix_span(buf), quote: false});
+    }
+    None
 }
 
+// packed date value, from the most significant bits:
+// year(18) month(4) day(5) hour(5) minute(6) second(6) microsecond(20)
+// the shortest timestamp, epoch seconds
+const MIN_LINE_LEN: usize = 10;
+
+pub(crate) const MICROS_BITS: u64 = 20;
+pub(crate) const MICROS_MASK: u64 = (1 << MICROS_BITS) - 1;
+
 //#[inline(never)]
-fn calc_u64(second: i16, minute: i16, hour: i16, day: i16, month: i16, year: i32) -> Option<u64> {
-    if (0..=59).contains(&second) & (0..=59).contains(&minute) & (0..=24).contains(&hour) & (0..=31).contains(&day) & (0..=12).contains(&month) & (1000..=4000).contains(&year)
+fn calc_u64(micro: u32, second: i16, minute: i16, hour: i16, day: i16, month: i16, year: i32) -> Option<u64> {
+    if (0..=59).contains(&second) & (0..=59).contains(&minute) & (0..=24).contains(&hour) & (0..=31).contains(&day) & (0..=12).contains(&month) & (1000..=4000).contains(&year) & (micro < 1_000_000)
     {
-        let mut value: u64 = second as u64;
-        value += (minute as u64) << 8;
-        value += (hour as u64) << 16;
-        value += (day as u64) << 24;
-        value += (month as u64) << 32;
-        value += (year as u64) << 40;
+        let mut value: u64 = micro as u64;
+        value += (second as u64) << 20;
+        value += (minute as u64) << 26;
+        value += (hour as u64) << 32;
+        value += (day as u64) << 37;
+        value += (month as u64) << 42;
+        value += (year as u64) << 46;
         return Some(value)
     }
     None
@@ -300,41 +638,46 @@ pub fn normalized_datetime_naive(buf: &[u8]) -> Option<NormRetValue> {
     if buf.len() < 19 {
         return None;
     }
-    // 24.12.22 00:10:27     carmen  17
-    let carmen_fmt = "%d.%m.%Y %H:%M:%S";
-    // 2023-01-26 09:32:28   yoda  19
-    let yoda_fmt = "%Y-%m-%d %H:%M:%S";
-    // 20230729111238        carmen, error log
-    let carmen_fmt_err = "%Y%m%d%H%M%S";
-
-    let line_str = unsafe { std::str::from_utf8_unchecked(buf) };
-    let dt = NaiveDateTime::parse_from_str(&line_str[..17], carmen_fmt);
-    match dt {
-        Ok(d) => Some(NormRetValue{date_value: normalize_bits(d), log_type:LogType::Carmen(17)}),
-        Err(_) => {
-            let dt = NaiveDateTime::parse_from_str(&line_str[..19], yoda_fmt);
-            match dt {
-                Ok(d) => Some(NormRetValue{date_value: normalize_bits(d), log_type:LogType::Yoda(19)}),
-                Err(_) => {
-                    let dt = NaiveDateTime::parse_from_str(&line_str[..14], carmen_fmt_err);
-                    match dt {
-                        Ok(d) => Some(NormRetValue{date_value: normalize_bits(d), log_type:LogType::CarmenErr(14)}),
-                        Err(_) => None,
-                    }
-                }
-            }
-        }
-    }
+    NAIVE_FORMATS.normalize(buf)
 }
 
-/// "%d.%m.%Y %H:%M:%S"
+/// "%d.%m.%Y %H:%M:%S" with optional fraction ",SSS" or ".SSSSSS", or ISO 8601
+/// with optional offset "2023-01-26T09:32:28.828+01:00"
+/// `round_up` lets a bound without fraction cover the whole second
 #[inline(never)]
-fn normalized_command_line_date(date_time: Option<&String>) -> Option<Result<u64, String>> {
+fn normalized_command_line_date(date_time: Option<&String>, round_up: bool, pivot: i32) -> Option<Result<(u64, Option<i32>), String>> {
     match date_time {
         Some(time) => {
-            let dt = NaiveDateTime::parse_from_str(time, "%d.%m.%Y %H:%M:%S");
+            if let Some(value) = relative_date(time, pivot) {
+                return Some(value.map(|value| (value, Some(0))));
+            }
+            let buf = time.as_bytes();
+            if Epoch.detect(buf) {
+                return match (Epoch::micros_and_end(buf), Epoch.parse(buf)) {
+                    // seconds cover the whole second, milliseconds the whole millisecond
+                    (Some((_, end)), Some(value)) if end == buf.len() => match end {
+                        10 if round_up => Some(Ok((value + 999_999, Some(0)))),
+                        13 if round_up => Some(Ok((value + 999, Some(0)))),
+                        _ => Some(Ok((value, Some(0)))),
+                    },
+                    _ => Some(Err(format!("couldn't parse dateTime: {time}"))),
+                };
+            }
+            if Iso.detect(buf) {
+                let (offset, end) = Iso::offset_and_end(buf);
+                return match Iso.parse(buf) {
+                    Some(value) if end == buf.len() => {
+                        let round_up = round_up && !format::has_fraction(buf, 19);
+                        Some(Ok((if round_up { value + 999_999 } else { value }, offset)))
+                    }
+                    _ => Some(Err(format!("couldn't parse dateTime: {time}"))),
+                };
+            }
+            let has_fraction = time.rsplit(':').next().is_some_and(|s| s.contains(['.', ',']));
+            let dt = NaiveDateTime::parse_from_str(&time.replace(',', "."), "%d.%m.%Y %H:%M:%S%.f");
             match dt {
-                Ok(d) => Some(Ok(normalize_bits(d))),
+                Ok(d) if round_up && !has_fraction => Some(Ok((normalize_bits(d, pivot) + 999_999, None))),
+                Ok(d) => Some(Ok((normalize_bits(d, pivot), None))),
                 Err(_) => Some(Err(format!("couldn't parse dateTime: {time}"))),
             }
         }
@@ -342,18 +685,62 @@ fn normalized_command_line_date(date_time: Option<&String>) -> Option<Result<u64
     }
 }
 
-// YYYYMMDDhhmmss
+/// Whether a start/end date carries its own UTC offset (ISO 8601 with Z or +hh:mm, epoch, now)
+pub fn command_line_date_has_offset(date_time: &str) -> bool {
+    let buf = date_time.as_bytes();
+    date_time.starts_with("now") || Epoch.detect(buf) || (Iso.detect(buf) && Iso.utc_offset(buf).is_some())
+}
+
+// "now", "now-5m", "now-2h" in UTC: s, m, h or d before now. None for other dates
+fn relative_date(time: &str, pivot: i32) -> Option<Result<u64, String>> {
+    let rest = time.strip_prefix("now")?;
+    let before = match rest.strip_prefix('-') {
+        None if rest.is_empty() => Some(chrono::Duration::zero()),
+        None => None,
+        Some(ago) => {
+            let (count, unit) = ago.split_at(ago.len().saturating_sub(1));
+            let seconds = match unit {
+                "s" => Some(1_i64),
+                "m" => Some(60),
+                "h" => Some(3600),
+                "d" => Some(86400),
+                _ => None,
+            };
+            // within the range of chrono::Duration
+            seconds
+                .zip(count.parse::<i64>().ok())
+                .and_then(|(seconds, count)| seconds.checked_mul(count))
+                .filter(|seconds| (0..1 << 40).contains(seconds))
+                .map(chrono::Duration::seconds)
+        }
+    };
+    Some(
+        before
+            .and_then(|before| chrono::Utc::now().naive_utc().checked_sub_signed(before))
+            .map(|date| normalize_bits(date, pivot))
+            .ok_or_else(|| format!("couldn't parse dateTime: {time}, relative dates are like now-5m (s, m, h, d)")),
+    )
+}
+
+// four digit year of a two digit one
+#[inline(always)]
+pub(crate) fn pivot_year(year: i32, pivot: i32) -> i32 {
+    pivot + (year - pivot % 100).rem_euclid(100)
+}
+
+// YYYYMMDDhhmmss + microseconds, two digit years are moved to the century starting at `pivot`
 //#[inline(always)]
-fn normalize_bits(d: NaiveDateTime) -> u64 {
-    let mut value: u64 = d.second() as u64;
-    value += (d.minute() as u64) << 8;
-    value += (d.hour() as u64) << 16;
-    value += (d.day() as u64) << 24;
-    value += (d.month() as u64) << 32;
+fn normalize_bits(d: NaiveDateTime, pivot: i32) -> u64 {
+    let mut value: u64 = (d.nanosecond() / 1000).min(999_999) as u64;
+    value += (d.second() as u64) << 20;
+    value += (d.minute() as u64) << 26;
+    value += (d.hour() as u64) << 32;
+    value += (d.day() as u64) << 37;
+    value += (d.month() as u64) << 42;
     if d.year() <= 99 {
-        value += ((d.year() + *CENTURY) as u64) << 40;
+        value += (pivot_year(d.year(), pivot) as u64) << 46;
     } else {
-        value += (d.year() as u64) << 40;
+        value += (d.year() as u64) << 46;
     }
     value
 }
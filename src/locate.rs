@@ -0,0 +1,46 @@
+use regex::bytes::Regex;
+
+/// Where the timestamp starts when the line has a prefix (hostname, container ID, ...).
+/// The formats see the line from there on, spans are relative to the whole line.
+#[derive(Clone, Debug)]
+pub enum Locator {
+    /// skip a fixed number of bytes
+    Bytes(usize),
+    /// skip a number of whitespace separated fields
+    Fields(usize),
+    /// start of the first capture group, or of the match without groups
+    Regex(Regex),
+}
+
+impl Locator {
+    pub fn regex(pattern: &str) -> Result<Locator, String> {
+        Regex::new(pattern)
+            .map(Locator::Regex)
+            .map_err(|e| format!("invalid regex: {pattern}: {e}"))
+    }
+
+    /// Index of the timestamp, None if the line has no such place
+    #[inline(always)]
+    pub fn start(&self, buf: &[u8]) -> Option<usize> {
+        match self {
+            Locator::Bytes(count) => (*count < buf.len()).then_some(*count),
+            Locator::Fields(count) => {
+                let mut idx = buf.iter().take_while(|b| is_blank(**b)).count();
+                for _ in 0..*count {
+                    idx += buf[idx..].iter().take_while(|b| !b.is_ascii_whitespace()).count();
+                    idx += buf[idx..].iter().take_while(|b| is_blank(**b)).count();
+                }
+                (idx < buf.len()).then_some(idx)
+            }
+            Locator::Regex(regex) => {
+                let captures = regex.captures(buf)?;
+                captures.get(1).or_else(|| captures.get(0)).map(|m| m.start())
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn is_blank(b: u8) -> bool {
+    (b == b' ') | (b == b'\t')
+}
@@ -1,9 +1,25 @@
-use std::time::SystemTime;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 use clap::Parser;
 use colored::Colorize;
-use fd::process_file;
+use fd::command_line_date_has_offset;
+use fd::{follow_file, process_file, Options};
 use fd::DateTimeHolder;
+use fd::files::{read_list, FileSelection};
+#[cfg(feature = "index")]
+use fd::index::Index;
+use fd::jobs::{self, FileOrder};
+use fd::DEFAULT_PIVOT_YEAR;
+use fd::format::{ChronoFormat, Epoch, Json, YearStrategy};
+use fd::locate::Locator;
+use fd::rotate;
+use fd::zone::{DstPolicy, Zone};
+use fd::FormatRegistry;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 
 // microsoft malloc
 #[cfg(feature = "win_only")]
@@ -13,7 +29,7 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 /// F. P.:  Program to filter carmen/yoda logfiles(byte encoded) by datetime. 
-/// Only till seconds (no millis and more)
+/// Down to microseconds (,SSS .SSS .SSSSSS behind the seconds)
 #[derive(Parser, Debug)]
 #[command(version)]
 struct CMDArgs {
@@ -29,19 +45,368 @@ struct CMDArgs {
     #[arg(short, long,)]
     replace: bool,
 
-    /// The optional start date: dd.mm.yyyy HH::MM:SS
+    /// The optional start date: dd.mm.yyyy HH::MM:SS[,SSS], ISO 8601 (2023-01-26T09:32:28.828+01:00), epoch (1674722048, 1674722048828) or relative (now-5m, s m h d)
     #[arg(short, long)]
     start: Option<String>,
 
-    /// The optional end date: dd.mm.yyyy HH::MM:SS
+    /// The optional end date: dd.mm.yyyy HH::MM:SS[,SSS], ISO 8601, epoch or relative, without fraction the whole second is included
     #[arg(short, long)]
     end: Option<String>,
 
-    /// Name of the files to filter
+    /// Additional timestamp layout as strftime pattern, tried before the builtin ones.
+    /// Optional column where the timestamp starts: [OFFSET@]PATTERN e.g. "16@%d/%m/%Y %H:%M:%S"
+    #[arg(long = "format", value_name = "FORMAT")]
+    formats: Vec<String>,
+
+    /// Only use the layouts given by --format
+    #[arg(long, requires = "formats")]
+    no_builtin: bool,
+
+    /// Time zone of the start/end date: local, UTC, +01:00, Europe/Berlin
+    #[arg(long, value_name = "ZONE")]
+    tz: Option<String>,
+
+    /// Time zone of the log lines, for one format or all: [FORMAT=]ZONE.
+    /// With any zone given everything is compared (and replaced) in UTC, undeclared zones are local
+    #[arg(long = "log-tz", value_name = "[FORMAT=]ZONE")]
+    log_tz: Vec<String>,
+
+    /// Local times which are ambiguous or missing around DST switches: earliest, latest, reject
+    /// or auto (follows the order of the lines)
+    #[arg(long, default_value = "auto", value_parser = DstPolicy::from_str)]
+    dst: DstPolicy,
+
+    /// Year of syslog lines (which have none): mtime (lines lie before the file modification),
+    /// rollover (like mtime for the first line, then counting December to January) or the YYYY of the first line
+    #[arg(long, default_value = "mtime", value_parser = YearStrategy::from_str)]
+    syslog_year: YearStrategy,
+
+    /// Two digit years (dd.mm.yy) fall into the century starting at this year, 1970: 70..99 is 1970..1999, 00..69 is 2000..2069
+    #[arg(long, value_name = "YYYY", default_value_t = DEFAULT_PIVOT_YEAR, value_parser = clap::value_parser!(i32).range(1000..=3900))]
+    pivot_year: i32,
+
+    /// Only real calendar dates (leap years, no hour 24, day 0, month 0 or 31.02.),
+    /// rejected lines are reported per reason and file
+    #[arg(long)]
+    strict: bool,
+
+    /// Lines without timestamp (stack traces) belong to the line before and are emitted or dropped with it
+    #[arg(long)]
+    records: bool,
+
+    /// Maximum lines of a record with --records, further lines are dropped
+    #[arg(long, value_name = "LINES", default_value_t = 1000, requires = "records")]
+    max_record_lines: usize,
+
+    /// Corrupt or truncated compressed files end with exit code 3 instead of 1. Either way
+    /// the files after them are read and all decodable lines are emitted
+    #[arg(long)]
+    recover: bool,
+
+    /// Lines sampled per input to pick its dominant format, which alone parses the rest.
+    /// 0 tries all formats on every line (inputs with mixed formats)
+    #[arg(long, value_name = "LINES", default_value_t = 100)]
+    sample: usize,
+
+    /// JSON Lines input, the timestamp is the value of @timestamp, ts or time
+    #[arg(long)]
+    json: bool,
+
+    /// Lines starting with epoch seconds, milliseconds or microseconds (1674722048, 1674722048828)
+    #[arg(long)]
+    epoch: bool,
+
+    /// Field of JSON lines with the timestamp, nested objects as "kubernetes.ts". Implies --json
+    #[arg(long = "json-field", value_name = "PATH")]
+    json_fields: Vec<String>,
+
+    /// Bytes before the timestamp, for lines with a fixed size prefix
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["skip_fields", "locate"])]
+    skip_bytes: Option<usize>,
+
+    /// Whitespace separated fields before the timestamp, e.g. hostname and container ID
+    #[arg(long, value_name = "FIELDS", conflicts_with = "locate")]
+    skip_fields: Option<usize>,
+
+    /// Regex locating the timestamp, it starts at the first capture group (or the match), e.g. "host\d+ (\d)"
+    #[arg(long, value_name = "REGEX")]
+    locate: Option<String>,
+
+    /// Glob on the member names of tar and zip archives, e.g. "*/carmen*.log*", all members without
+    #[arg(long = "member", value_name = "GLOB")]
+    members: Vec<String>,
+
+    /// Files found in directories are only taken if their path matches, e.g. "*.log*"
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Files and directories skipped in directories, e.g. "*/archive/*"
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// File with the names of the files to filter, one per line. - reads NUL separated names from stdin (find -print0)
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<String>,
+
+    /// Rotated files (server.log.2.gz, server.log.1, server.log-20230126, server.log) are
+    /// processed oldest to newest, whatever their order in the arguments
+    #[arg(long)]
+    rotated: bool,
+
+    /// Skip rotated files whose first and last timestamp lie outside the start/end date
+    #[arg(long, requires = "rotated")]
+    skip_outside: bool,
+
+    /// Bisect sorted uncompressed files for the start date instead of reading them from the
+    /// first line, files which turn out unsorted are read from the start
+    #[arg(long)]
+    seek: bool,
+
+    /// Map uncompressed files into memory instead of reading them through a buffer. Not for
+    /// files which may be truncated while they are read (copytruncate), that ends fd
+    #[arg(long)]
+    mmap: bool,
+
+    /// Filter N files at a time (0: one per core), the output still comes file by file,
+    /// large outputs wait in temp files
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// Order of the files in the output: input (as given) or time (by their first timestamp)
+    #[arg(long, default_value = "input", value_parser = FileOrder::from_str)]
+    order: FileOrder,
+
+    /// Keep reading the file as it grows like tail -F, across rotation and truncation. Takes one
+    /// file or one rotated set (--rotated) whose current file is followed, not stdin or --order time.
+    /// Ends after a line behind the end date, e.g. with -s "now-5m"
+    #[arg(long)]
+    follow: bool,
+
+    /// End --follow after this many seconds without new lines
+    #[arg(long, value_name = "SECONDS", requires = "follow")]
+    idle_timeout: Option<u64>,
+
+    /// Write an index sidecar (FILE.gz.fdx) for the given gzip files instead of filtering, later
+    /// runs with a start date inflate from the checkpoint before it instead of from the beginning.
+    /// The log time zone options apply to the timestamps of the index, queries with other ones
+    /// (or a start date with offset without --tz) don't use it
+    #[arg(long, requires = "files")]
+    build_index: bool,
+
+    /// Uncompressed MiB between the checkpoints of --build-index
+    #[arg(long, value_name = "MIB", default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..), requires = "build_index")]
+    span: u64,
+
+    /// Name of the files to filter, directories are searched recursively (sorted by name)
     #[clap(value_parser)]
     files: Option<Vec<String>>,
 }
 
+// exit code with --recover when an input was corrupt
+const EXIT_CORRUPT: i32 = 3;
+
+fn build_formats(args: &CMDArgs) -> FormatRegistry {
+    let mut formats = FormatRegistry::new();
+    for spec in &args.formats {
+        match ChronoFormat::from_spec(spec) {
+            Ok(format) => formats.register(Box::new(format)),
+            Err(e) => {
+                eprintln!("{}", e.bold().red());
+                ::std::process::exit(1);
+            }
+        }
+    }
+    if args.json || !args.json_fields.is_empty() {
+        formats.register(Box::new(Json::new(args.json_fields.clone(), args.fast)));
+    }
+    if !args.no_builtin {
+        formats.append(FormatRegistry::builtin(args.fast));
+    }
+    if args.epoch {
+        formats.register(Box::new(Epoch));
+    }
+    formats.set_dst_policy(args.dst);
+    formats.set_year_strategy(args.syslog_year);
+    formats.set_sample_lines(args.sample);
+    formats.set_pivot_year(args.pivot_year);
+    if let Some(count) = args.skip_bytes {
+        formats.set_locator(Locator::Bytes(count));
+    }
+    if let Some(count) = args.skip_fields {
+        formats.set_locator(Locator::Fields(count));
+    }
+    if let Some(pattern) = &args.locate {
+        match Locator::regex(pattern) {
+            Ok(locator) => formats.set_locator(locator),
+            Err(e) => {
+                eprintln!("{}", e.bold().red());
+                ::std::process::exit(1);
+            }
+        }
+    }
+    for spec in &args.log_tz {
+        let (name, zone) = match spec.split_once('=') {
+            Some((name, zone)) => (Some(name), zone),
+            None => (None, spec.as_str()),
+        };
+        let zone = parse_zone(zone);
+        match name {
+            Some(name) => {
+                if formats.set_zone(name, &zone) == 0 {
+                    eprintln!("{}{}", "Unknown format: ".bold().red(), name.bold().red());
+                    ::std::process::exit(1);
+                }
+            }
+            None => formats.set_default_zone(&zone),
+        }
+    }
+    if zones_enabled(args) {
+        formats.set_default_zone(&Zone::Local);
+    }
+    formats
+}
+
+fn build_options(args: &CMDArgs) -> Options {
+    Options {
+        debug: args.debug,
+        replace: args.replace,
+        strict: args.strict,
+        record_lines: if args.records { args.max_record_lines } else { 0 },
+        members: (!args.members.is_empty()).then(|| glob_set(&args.members)),
+        seek: args.seek,
+        mmap: args.mmap,
+    }
+}
+
+fn glob_set(patterns: &[String]) -> GlobSet {
+    let mut globs = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => globs.add(glob),
+            Err(e) => {
+                eprintln!("{}", format!("invalid glob: {pattern}: {e}").bold().red());
+                ::std::process::exit(1);
+            }
+        };
+    }
+    match globs.build() {
+        Ok(globs) => globs,
+        Err(e) => {
+            eprintln!("{}", e.to_string().bold().red());
+            ::std::process::exit(1);
+        }
+    }
+}
+
+// the files of the arguments and --files-from, directories expanded
+fn input_files(args: &CMDArgs) -> Option<Vec<String>> {
+    if args.files.is_none() && args.files_from.is_none() {
+        return None;
+    }
+    let mut paths = args.files.clone().unwrap_or_default();
+    if let Some(list) = &args.files_from {
+        let listed = if list == "-" {
+            read_list(std::io::stdin(), true)
+        } else {
+            File::open(list).and_then(|file| read_list(file, false))
+        };
+        match listed {
+            Ok(listed) => paths.extend(listed),
+            Err(e) => {
+                eprintln!("{}: {}", list.bold(), e.to_string().bold().red());
+                ::std::process::exit(1);
+            }
+        }
+    }
+    let include = (!args.include.is_empty()).then(|| glob_set(&args.include));
+    let exclude = (!args.exclude.is_empty()).then(|| glob_set(&args.exclude));
+    Some(FileSelection::new(include, exclude).expand(&paths))
+}
+
+// rotated sets oldest to newest, without the files outside the window
+fn rotated_files(args: &CMDArgs, files: Vec<String>, start_end_date: &DateTimeHolder, formats: &FormatRegistry) -> Vec<String> {
+    let files = rotate::order(files);
+    if args.skip_outside {
+        rotate::skip_outside(files, start_end_date, formats, args.debug)
+    } else {
+        files
+    }
+}
+
+// the live file of --follow, taken out of the files: the only one or the current file of the
+// only rotated set. Exits for stdin, --order time and several inputs, these have no live file
+fn followed_file(args: &CMDArgs, files: &mut Option<Vec<String>>) -> String {
+    let error = match files {
+        None => "--follow reads a file, not stdin",
+        Some(_) if args.order == FileOrder::Time => "--follow keeps the files in the order given, not with --order time",
+        Some(files) if args.rotated && files.iter().map(|file| rotate::Rotation::of(file).0).collect::<HashSet<_>>().len() == 1 => {
+            // the current file is the newest of the set
+            let live = rotate::order(files.clone()).pop().unwrap();
+            files.retain(|file| *file != live);
+            return live;
+        }
+        Some(files) if files.len() == 1 => return files.pop().unwrap(),
+        Some(_) => "--follow reads one file or one rotated set (--rotated)",
+    };
+    eprintln!("{}", error.bold().red());
+    ::std::process::exit(1);
+}
+
+// threads for -j, 0 is one per core
+fn job_count(jobs: usize) -> usize {
+    match jobs {
+        0 => std::thread::available_parallelism().map_or(1, |count| count.get()),
+        jobs => jobs,
+    }
+}
+
+fn parse_zone(zone: &str) -> Zone {
+    match Zone::from_str(zone) {
+        Ok(zone) => zone,
+        Err(e) => {
+            eprintln!("{}", e.bold().red());
+            ::std::process::exit(1);
+        }
+    }
+}
+
+fn zones_enabled(args: &CMDArgs) -> bool {
+    args.tz.is_some()
+        || !args.log_tz.is_empty()
+        || args.start.iter().chain(args.end.iter()).any(|date| command_line_date_has_offset(date))
+}
+
+// writes the sidecars, exit code 1 if a file couldn't be indexed
+#[cfg(feature = "index")]
+fn index_files(args: &CMDArgs, files: &[String], span: u64) -> i32 {
+    let formats = build_formats(args);
+    let mut exit_code = 0;
+    for file in files {
+        let sidecar = Index::sidecar(file);
+        match Index::build(file, &formats, span * 1_048_576).and_then(|index| {
+            index.write(&sidecar).map_err(|e| e.to_string())?;
+            Ok(index)
+        }) {
+            Ok(index) => {
+                if args.debug > 0 {
+                    eprintln!("{}: {} checkpoints", sidecar, index.checkpoints.len());
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", file.bold(), e.red());
+                exit_code = 1;
+            }
+        }
+    }
+    exit_code
+}
+
+#[cfg(not(feature = "index"))]
+fn index_files(_args: &CMDArgs, _files: &[String], _span: u64) -> i32 {
+    eprintln!("{}", "index support is not compiled in (cargo feature index)".bold().red());
+    1
+}
+
 fn check_consistency_of_args(args: &CMDArgs) {
     if args.start.is_none() && args.end.is_none() {
         eprintln!("{}", "Start- or End- Date must be given".bold().red());
@@ -53,10 +418,18 @@ fn check_consistency_of_args(args: &CMDArgs) {
 fn main() {
     let args = CMDArgs::parse();
 
+    if args.build_index {
+        ::std::process::exit(index_files(&args, args.files.as_deref().unwrap_or_default(), args.span));
+    }
     check_consistency_of_args(&args);
 
+    let zone = if zones_enabled(&args) {
+        Some(parse_zone(args.tz.as_deref().unwrap_or("local")))
+    } else {
+        None
+    };
     let start_end_date: DateTimeHolder =
-        DateTimeHolder::new(args.start.as_ref(), args.end.as_ref());
+        DateTimeHolder::with_options(args.start.as_ref(), args.end.as_ref(), zone.as_ref(), args.dst, args.pivot_year);
 
     if !start_end_date.validate() {
         eprintln!(
@@ -65,36 +438,71 @@ fn main() {
         );
         ::std::process::exit(1);
     }
+    let formats = build_formats(&args);
+    let options = build_options(&args);
     let now = SystemTime::now();
-    if args.files.is_none() {
-        process_file(
-            &start_end_date,
-            None,
-            args.debug,
-            args.fast,
-            args.replace,
-            &mut std::io::stdout(),
-            &mut std::io::stdin(),
-        );
-    } else {
-        let len = args.files.as_ref().unwrap().len();
-        for idx in 0..len {
-            let filename = &args.files.as_ref().unwrap()[idx];
-            process_file(
+    let mut corrupt = false;
+    let mut files = input_files(&args);
+    let followed = args.follow.then(|| followed_file(&args, &mut files));
+    if args.rotated {
+        files = files.map(|files| rotated_files(&args, files, &start_end_date, &formats));
+    }
+    files = files.map(|files| args.order.sort(files, &formats));
+    match &files {
+        None => {
+            corrupt |= process_file(
                 &start_end_date,
-                Some(filename),
-                args.debug,
-                args.fast,
-                args.replace,
+                None,
+                &formats,
+                &options,
                 &mut std::io::stdout(),
                 &mut std::io::stdin(),
-            );
+            )
+            .is_some();
+        }
+        Some(files) if job_count(args.jobs) > 1 => {
+            let filter = |filename: &str, spool: &mut jobs::Spool| {
+                process_file(&start_end_date, Some(filename), &formats, &options, spool, &mut std::io::empty()).is_some()
+            };
+            jobs::in_order(files, job_count(args.jobs), filter, |filename, spool, failed| {
+                if args.debug > 0 && spool.spilled() {
+                    eprintln!("{}: output waited in a temp file", filename);
+                }
+                if let Err(e) = spool.copy_to(&mut std::io::stdout()) {
+                    eprintln!("{:?}", e);
+                    ::std::process::exit(1);
+                }
+                corrupt |= failed;
+            });
         }
+        Some(files) => {
+            for filename in files {
+                corrupt |= process_file(
+                    &start_end_date,
+                    Some(filename),
+                    &formats,
+                    &options,
+                    &mut std::io::stdout(),
+                    &mut std::io::stdin(),
+                )
+                .is_some();
+            }
+        }
+    }
+    // the live file comes after the others
+    if let Some(filename) = &followed {
+        let idle = args.idle_timeout.map(Duration::from_secs);
+        corrupt |= follow_file(&start_end_date, filename, &formats, &options, &mut std::io::stdout(), idle).is_some();
     }
     if args.debug > 0 {
         let duration = now.elapsed().expect("Clock error ?!").as_millis() as u64;
         eprintln!("Processing took {} ms", duration);
     }
 
-    ::std::process::exit(0);
+    let _ = std::io::stdout().flush();
+    ::std::process::exit(match (corrupt, args.recover) {
+        (false, _) => 0,
+        (true, true) => EXIT_CORRUPT,
+        (true, false) => 1,
+    });
 }
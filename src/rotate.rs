@@ -0,0 +1,164 @@
+use colored::Colorize;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+use crate::archive::Archive;
+use crate::decode::{self, Compression};
+use crate::format::FormatRegistry;
+use crate::DateTimeHolder;
+
+// lines read for the first timestamp of a file
+const HEAD_LINES: usize = 1000;
+
+// bytes at the end of a plain file searched for the last timestamp
+const TAIL_BYTES: u64 = 65_536;
+
+lazy_static! {
+    // base name, dated suffix (dateext: -20230126, .2023-01-26, -2023012613) or
+    // numbered suffix (.1), compression
+    static ref ROTATED: Regex = Regex::new(
+        r"^(.*?)(?:[._-](\d{4})-?(\d{2})-?(\d{2})(?:[_-]?(\d{2,6}))?|\.(\d{1,4}))?(\.(?:gz|zst|zstd|xz|bz2|lz4))?$"
+    )
+    .unwrap();
+}
+
+/// Position of a file in its rotated set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    /// dateext suffix, YYYYMMDD and the time digits if any
+    Dated(u64),
+    /// logrotate number, .1 is the newest rotated file
+    Numbered(u32),
+    /// the file written to
+    Current,
+}
+
+impl Rotation {
+    /// Base name of the set and the position, "server.log.2.gz" is ("server.log", Numbered(2))
+    pub fn of(file_name: &str) -> (&str, Rotation) {
+        let captures = match ROTATED.captures(file_name) {
+            Some(captures) => captures,
+            None => return (file_name, Rotation::Current),
+        };
+        let base = captures.get(1).map_or(file_name, |base| base.as_str());
+        if base.is_empty() {
+            return (file_name, Rotation::Current);
+        }
+        let number = |idx: usize| captures.get(idx).map(|digits| digits.as_str().parse::<u64>().unwrap());
+        let rotation = if let Some(year) = number(2) {
+            // time digits left aligned: 13 is 130000
+            let time = captures.get(5).map_or(0, |time| {
+                time.as_str().parse::<u64>().unwrap() * 10_u64.pow(6 - time.len() as u32)
+            });
+            Rotation::Dated(((year * 100 + number(3).unwrap()) * 100 + number(4).unwrap()) * 1_000_000 + time)
+        } else if let Some(number) = number(6) {
+            Rotation::Numbered(number as u32)
+        } else {
+            Rotation::Current
+        };
+        (base, rotation)
+    }
+
+    // oldest first
+    fn age(&self) -> (u8, u64) {
+        match self {
+            Rotation::Dated(date) => (0, *date),
+            Rotation::Numbered(number) => (1, u64::MAX - *number as u64),
+            Rotation::Current => (2, 0),
+        }
+    }
+}
+
+/// Rotated sets oldest to newest: dated files, the numbered ones from the highest number
+/// and the current file. The sets keep the order in which they first appear.
+pub fn order(files: Vec<String>) -> Vec<String> {
+    let mut sets: Vec<Vec<(Rotation, String)>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for file in files {
+        let (base, rotation) = Rotation::of(&file);
+        let idx = *index.entry(base.to_string()).or_insert_with(|| {
+            sets.push(Vec::new());
+            sets.len() - 1
+        });
+        sets[idx].push((rotation, file));
+    }
+    sets.into_iter()
+        .flat_map(|mut set| {
+            set.sort_by_key(|(rotation, _)| rotation.age());
+            set.into_iter().map(|(_, file)| file)
+        })
+        .collect()
+}
+
+/// Drops files of ordered sets without lines in the window: those starting after the end and
+/// those ending before the start. The end of a compressed file is where its successor starts.
+pub fn skip_outside(files: Vec<String>, start_end_date: &DateTimeHolder, formats: &FormatRegistry, debug: u8) -> Vec<String> {
+    let firsts: Vec<Option<u64>> = files.iter().map(|file| first_timestamp(file, formats)).collect();
+    let mut kept = Vec::with_capacity(files.len());
+    for (idx, file) in files.iter().enumerate() {
+        let (base, _) = Rotation::of(file);
+        let last = last_timestamp(file, formats).or_else(|| {
+            files
+                .get(idx + 1)
+                .filter(|next| Rotation::of(next).0 == base)
+                .and_then(|_| firsts[idx + 1])
+        });
+        let after = firsts[idx].is_some_and(|first| first > start_end_date.end);
+        let before = last.is_some_and(|last| last < start_end_date.start);
+        if after || before {
+            if debug > 0 {
+                eprintln!("{}: {}", file, "skipped, outside the window".bright_red());
+            }
+        } else {
+            kept.push(file.clone());
+        }
+    }
+    kept
+}
+
+/// First timestamp within the first lines, None for archives and unreadable files
+pub fn first_timestamp(file_name: &str, formats: &FormatRegistry) -> Option<u64> {
+    let file = File::open(file_name).ok()?;
+    let reference = file.metadata().and_then(|m| m.modified()).ok();
+    let mut decoded = decode::reader(file).ok()?;
+    if Archive::sniff(decoded.reader.fill_buf().ok()?).is_some() {
+        return None;
+    }
+    let mut state = formats.input_state(reference);
+    let mut buf = Vec::new();
+    for _ in 0..HEAD_LINES {
+        buf.clear();
+        if decoded.reader.read_until(b'\n', &mut buf).ok()? == 0 {
+            return None;
+        }
+        if let Some(value) = formats.normalize_in(&buf, &mut state) {
+            return Some(value.date_value);
+        }
+    }
+    None
+}
+
+/// Last timestamp of a plain file, from its tail. None for compressed files and archives
+pub fn last_timestamp(file_name: &str, formats: &FormatRegistry) -> Option<u64> {
+    let mut file = File::open(file_name).ok()?;
+    let metadata = file.metadata().ok()?;
+    let mut head = Vec::new();
+    (&mut file).take(512).read_to_end(&mut head).ok()?;
+    if Compression::sniff(&head) != Compression::None || Archive::sniff(&head).is_some() {
+        return None;
+    }
+    file.seek(SeekFrom::Start(metadata.len().saturating_sub(TAIL_BYTES))).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    let mut state = formats.input_state(metadata.modified().ok());
+    // the first line may be cut
+    let skip = if metadata.len() > TAIL_BYTES { 1 } else { 0 };
+    tail.split_inclusive(|b| *b == b'\n')
+        .skip(skip)
+        .filter_map(|line| formats.normalize_in(line, &mut state))
+        .map(|value| value.date_value)
+        .last()
+}
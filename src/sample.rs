@@ -0,0 +1,114 @@
+use colored::Colorize;
+
+use crate::format::{FormatRegistry, InputState};
+use crate::NormRetValue;
+
+// share of the parsed sample lines the dominant format needs, below the input counts as mixed
+const MIN_CONFIDENCE: f64 = 0.9;
+
+// lines of another format in a row the dominant one can't parse before the lines are sampled
+// again, lines without any timestamp (stack traces) don't count
+const MAX_FAILURES: usize = 16;
+
+/// Detection per input: the first lines are tried with all formats, then
+/// the dominant one alone parses the rest. This saves trying the other formats
+/// and payload which happens to look like another format isn't taken for a timestamp.
+pub struct Sampler<'a> {
+    formats: &'a FormatRegistry,
+    name: &'a str,
+    debug: u8,
+    counts: Vec<usize>,
+    sampled: usize,
+    dominant: Option<usize>,
+    failures: usize,
+    mixed: bool,
+}
+
+impl<'a> Sampler<'a> {
+    /// `name` and `debug` are for the report of the decision
+    pub fn new(formats: &'a FormatRegistry, name: &'a str, debug: u8) -> Sampler<'a> {
+        Sampler {
+            formats,
+            name,
+            debug,
+            counts: vec![0; formats.formats().len()],
+            sampled: 0,
+            dominant: None,
+            failures: 0,
+            mixed: false,
+        }
+    }
+
+    /// Index of the format used for all lines, once decided
+    pub fn dominant(&self) -> Option<usize> {
+        self.dominant
+    }
+
+    pub fn normalize(&mut self, buf: &[u8], state: &mut InputState) -> Option<NormRetValue> {
+        if let Some(idx) = self.dominant {
+            let value = self.formats.normalize_by(idx, buf, state);
+            if value.is_some() {
+                self.failures = 0;
+                return value;
+            }
+            // tried on a copy, the payload mustn't move the state
+            self.formats.detect_in(buf, &mut state.clone())?;
+            self.failures += 1;
+            if self.failures < MAX_FAILURES {
+                return None;
+            }
+            if self.debug > 0 {
+                eprintln!("{}: {} failed {} times, sampling again", self.name, self.formats.formats()[idx].name(), MAX_FAILURES);
+            }
+            self.dominant = None;
+            self.failures = 0;
+        }
+
+        let value = self.formats.detect_in(buf, state);
+        if self.formats.sample_lines() > 0 && !self.mixed {
+            if let Some((idx, _)) = value {
+                self.counts[idx] += 1;
+            }
+            self.sampled += 1;
+            if self.sampled >= self.formats.sample_lines() {
+                self.decide();
+            }
+        }
+        value.map(|(_, value)| value)
+    }
+
+    /// Reports the sample of an input shorter than the sample size
+    pub fn finish(&mut self) {
+        if self.dominant.is_none() && !self.mixed && self.sampled > 0 {
+            self.decide();
+        }
+    }
+
+    fn decide(&mut self) {
+        let parsed: usize = self.counts.iter().sum();
+        if parsed > 0 {
+            let (idx, hits) = self.counts.iter().enumerate().max_by_key(|(idx, hits)| (**hits, usize::MAX - idx)).unwrap();
+            let confidence = *hits as f64 / parsed as f64;
+            if self.debug > 0 {
+                eprintln!(
+                    "{}: {} {:.0}% of {} parsed lines, {} sampled",
+                    self.name,
+                    self.formats.formats()[idx].name().bold(),
+                    confidence * 100.0,
+                    parsed,
+                    self.sampled
+                );
+            }
+            if confidence >= MIN_CONFIDENCE {
+                self.dominant = Some(idx);
+            } else {
+                self.mixed = true;
+                if self.debug > 0 {
+                    eprintln!("{}: {}", self.name, "mixed formats, detecting on every line".bright_red());
+                }
+            }
+        }
+        self.counts.iter_mut().for_each(|hits| *hits = 0);
+        self.sampled = 0;
+    }
+}
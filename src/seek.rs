@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::time::SystemTime;
+
+use crate::format::FormatRegistry;
+
+// range left to the linear scan
+const MIN_RANGE: u64 = 65_536;
+
+// probes spread over the file before bisecting
+const SAMPLES: u64 = 8;
+
+// lines read at a probe for a timestamp
+const PROBE_LINES: usize = 1000;
+
+/// Offset of a line before the first one at or after `start` in a sorted plain file,
+/// found by bisecting the file by bytes. 0 if the probes show the file isn't sorted.
+pub fn start_offset(file: &mut File, start: u64, formats: &FormatRegistry, reference: Option<SystemTime>) -> std::io::Result<u64> {
+    let len = file.metadata()?.len();
+    if len <= MIN_RANGE {
+        return Ok(0);
+    }
+    // timestamps at the probed offsets, they must grow with the offset
+    let mut probes = Vec::new();
+    match probe(file, 0, len, formats, reference)? {
+        Some((_, value)) if value >= start => return Ok(0),
+        Some(found) => probes.push(found),
+        None => return Ok(0),
+    }
+    // spread over the file, a bisection alone doesn't see concatenated files
+    for offset in (1..SAMPLES).map(|part| len / SAMPLES * part).chain([len.saturating_sub(MIN_RANGE)]) {
+        if let Some(found) = probe(file, offset, len, formats, reference)? {
+            probes.push(found);
+        }
+    }
+    let mut lo = 0;
+    let mut hi = len;
+    while hi - lo > MIN_RANGE {
+        let mid = lo + (hi - lo) / 2;
+        match probe(file, mid, hi, formats, reference)? {
+            Some((offset, value)) => {
+                probes.push((offset, value));
+                if value < start {
+                    lo = offset;
+                } else {
+                    hi = mid;
+                }
+            }
+            // lines without timestamps up to hi
+            None => hi = mid,
+        }
+    }
+    probes.sort_unstable();
+    if probes.windows(2).any(|pair| pair[0].1 > pair[1].1) {
+        return Ok(0);
+    }
+    Ok(lo)
+}
+
+// offset and value of the first timestamp after the line cut by `offset`, None if there's none before `end`
+fn probe(file: &mut File, offset: u64, end: u64, formats: &FormatRegistry, reference: Option<SystemTime>) -> std::io::Result<Option<(u64, u64)>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::with_capacity(MIN_RANGE as usize, file);
+    let mut buf = Vec::new();
+    let mut offset = offset;
+    if offset > 0 {
+        offset += reader.read_until(b'\n', &mut buf)? as u64;
+    }
+    let mut state = formats.input_state(reference);
+    for _ in 0..PROBE_LINES {
+        buf.clear();
+        let len = reader.read_until(b'\n', &mut buf)?;
+        if len == 0 || offset >= end {
+            break;
+        }
+        if let Some(value) = formats.normalize_in(&buf, &mut state) {
+            return Ok(Some((offset, value.date_value)));
+        }
+        offset += len as u64;
+    }
+    Ok(None)
+}
@@ -0,0 +1,87 @@
+use chrono::naive::NaiveDate;
+use colored::Colorize;
+
+use crate::unpack;
+
+// line numbers listed per reason
+const EXAMPLES: usize = 5;
+
+/// Why a line has no valid timestamp in strict mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// month 0 or above 12
+    Month,
+    /// day 0 or beyond the end of the month, e.g. 31.02. or 29.02. outside leap years
+    Day,
+    /// hour 24
+    Hour,
+    /// no format found a timestamp
+    NoTimestamp,
+}
+
+impl Rejection {
+    const ALL: [Rejection; 4] = [Rejection::Month, Rejection::Day, Rejection::Hour, Rejection::NoTimestamp];
+
+    /// Reason a packed date value isn't a real calendar date, None if it is one
+    pub fn of(date_value: u64) -> Option<Rejection> {
+        let (year, month, day, hour, _, _, _) = unpack(date_value);
+        if !(1..=12).contains(&month) {
+            Some(Rejection::Month)
+        } else if NaiveDate::from_ymd_opt(year as i32, month as u32, day as u32).is_none() {
+            Some(Rejection::Day)
+        } else if hour > 23 {
+            Some(Rejection::Hour)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Rejection::Month => "invalid month",
+            Rejection::Day => "invalid day",
+            Rejection::Hour => "hour 24",
+            Rejection::NoTimestamp => "no timestamp",
+        }
+    }
+}
+
+/// Rejected lines of one input, counted per reason
+#[derive(Default)]
+pub struct RejectionReport {
+    counts: [usize; 4],
+    lines: [Vec<usize>; 4],
+}
+
+impl RejectionReport {
+    pub fn new() -> RejectionReport {
+        RejectionReport::default()
+    }
+
+    /// `line` counts from 1
+    pub fn add(&mut self, reason: Rejection, line: usize) {
+        let idx = reason as usize;
+        self.counts[idx] += 1;
+        if self.lines[idx].len() < EXAMPLES {
+            self.lines[idx].push(line);
+        }
+    }
+
+    pub fn count(&self, reason: Rejection) -> usize {
+        self.counts[reason as usize]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// One line per reason on stderr
+    pub fn print(&self, name: &str) {
+        eprintln!("{}: {} lines rejected", name.bold(), self.total());
+        for reason in Rejection::ALL.iter().filter(|reason| self.count(**reason) > 0) {
+            let lines: Vec<String> = self.lines[*reason as usize].iter().map(|line| line.to_string()).collect();
+            let more = if self.count(*reason) > EXAMPLES { ", ..." } else { "" };
+            eprintln!("  {}: {} (lines {}{})", reason.name().bright_red(), self.count(*reason), lines.join(", "), more);
+        }
+    }
+}
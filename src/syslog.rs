@@ -0,0 +1,206 @@
+use chrono::naive::NaiveTime;
+use std::io::Write;
+use std::ops::Range;
+
+use crate::format::{is_digit, two_digits, InputState, Iso, LogFormat};
+use crate::{calc_u64, unpack};
+
+pub(crate) const MONTHS: [&[u8; 3]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+// index behind a leading <PRI>
+fn pri_end(buf: &[u8]) -> Option<usize> {
+    if buf.first() != Some(&b'<') {
+        return None;
+    }
+    let digits = buf[1..].iter().take(4).take_while(|b| is_digit(**b)).count();
+    if (1..=3).contains(&digits) && buf.get(digits + 1) == Some(&b'>') {
+        Some(digits + 2)
+    } else {
+        None
+    }
+}
+
+// the token starting at idx and the index of the next one
+fn token(buf: &[u8], idx: usize) -> Option<(&[u8], usize)> {
+    if idx >= buf.len() {
+        return None;
+    }
+    let len = buf[idx..].iter().take_while(|b| !b.is_ascii_whitespace()).count();
+    if len == 0 {
+        return None;
+    }
+    Some((&buf[idx..idx + len], idx + len + 1))
+}
+
+/// BSD syslog: [<PRI>]Jan 26 09:32:28 host prog[123]: message
+///
+/// The line has no year, it is inferred by the `YearStrategy` of the input.
+/// Fields: host, program, pid
+pub struct Syslog3164;
+
+impl Syslog3164 {
+    fn start(buf: &[u8]) -> usize {
+        pri_end(buf).unwrap_or(0)
+    }
+
+    fn month(buf: &[u8], idx: usize) -> Option<u32> {
+        MONTHS
+            .iter()
+            .position(|m| buf[idx..idx + 3] == m[..])
+            .map(|m| m as u32 + 1)
+    }
+
+    // month, day, hour, minute, second
+    fn fields_of_date(buf: &[u8]) -> Option<(u32, i16, i16, i16, i16)> {
+        let idx = Syslog3164::start(buf);
+        let month = Syslog3164::month(buf, idx)?;
+        let day = if buf[idx + 4] == b' ' {
+            (buf[idx + 5] as i16) - 48
+        } else {
+            two_digits(buf, idx + 4)
+        };
+        let hour = two_digits(buf, idx + 7);
+        let minute = two_digits(buf, idx + 10);
+        let second = two_digits(buf, idx + 13);
+        Some((month, day, hour, minute, second))
+    }
+
+    // host and tag behind the timestamp
+    fn header(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+        let (host, idx) = token(buf, Syslog3164::start(buf) + 16)?;
+        let (tag, _) = token(buf, idx)?;
+        Some((host, tag))
+    }
+}
+
+impl LogFormat for Syslog3164 {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        let idx = Syslog3164::start(buf);
+        (buf.len() >= idx + 15)
+            && (buf[idx + 3] == b' ')
+            && ((buf[idx + 4] == b' ') | is_digit(buf[idx + 4]))
+            && is_digit(buf[idx + 5])
+            && (buf[idx + 6] == b' ')
+            && (buf[idx + 9] == b':')
+            && (buf[idx + 12] == b':')
+            && (is_digit(buf[idx + 7])
+                & is_digit(buf[idx + 8])
+                & is_digit(buf[idx + 10])
+                & is_digit(buf[idx + 11])
+                & is_digit(buf[idx + 13])
+                & is_digit(buf[idx + 14]))
+            && Syslog3164::month(buf, idx).is_some()
+    }
+
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        self.parse_with(buf, &mut InputState::default())
+    }
+
+    fn parse_with(&self, buf: &[u8], state: &mut InputState) -> Option<u64> {
+        let (month, day, hour, minute, second) = Syslog3164::fields_of_date(buf)?;
+        let time = NaiveTime::from_hms_opt(hour as u32, minute as u32, second as u32)?;
+        let year = state.infer_year(month, day as u32, time);
+        calc_u64(0, second, minute, hour, day, month as i16, year)
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        let idx = Syslog3164::start(buf);
+        idx..idx + 15
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        let (_, month, day, hour, minute, second, _) = unpack(date_value);
+        let month = MONTHS[(month as usize).clamp(1, 12) - 1];
+        let month = std::str::from_utf8(month).unwrap_or("???");
+        write!(output, "{month} {day:>2} {hour:02}:{minute:02}:{second:02}")
+    }
+
+    fn field<'a>(&self, buf: &'a [u8], name: &str) -> Option<&'a [u8]> {
+        let (host, tag) = Syslog3164::header(buf)?;
+        let tag = tag.strip_suffix(b":").unwrap_or(tag);
+        let (program, pid) = match tag.iter().position(|b| *b == b'[') {
+            Some(pos) => (&tag[..pos], tag[pos + 1..].strip_suffix(b"]")),
+            None => (tag, None),
+        };
+        match name {
+            "host" => Some(host),
+            "program" => Some(program),
+            "pid" => pid,
+            _ => None,
+        }
+    }
+}
+
+/// IETF syslog: <PRI>1 2023-01-26T09:32:28.828+01:00 host app 123 msgid - message
+///
+/// Fields: host, program, pid, msgid (absent when given as "-")
+pub struct Syslog5424;
+
+impl Syslog5424 {
+    // start of the timestamp
+    fn start(buf: &[u8]) -> Option<usize> {
+        let idx = pri_end(buf)?;
+        let version = buf[idx..].iter().take(3).take_while(|b| is_digit(**b)).count();
+        if (1..=2).contains(&version) && buf.get(idx + version) == Some(&b' ') {
+            Some(idx + version + 1)
+        } else {
+            None
+        }
+    }
+}
+
+impl LogFormat for Syslog5424 {
+    fn name(&self) -> &str {
+        "syslog5424"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        match Syslog5424::start(buf) {
+            Some(idx) => Iso.detect(&buf[idx..]),
+            None => false,
+        }
+    }
+
+    fn parse(&self, buf: &[u8]) -> Option<u64> {
+        Iso.parse(&buf[Syslog5424::start(buf)?..])
+    }
+
+    fn prefix_span(&self, buf: &[u8]) -> Range<usize> {
+        let idx = Syslog5424::start(buf).unwrap_or(0);
+        let span = Iso.prefix_span(&buf[idx..]);
+        idx + span.start..idx + span.end
+    }
+
+    fn render(&self, date_value: u64, output: &mut dyn Write) -> std::io::Result<()> {
+        Iso.render(date_value, output)
+    }
+
+    fn utc_offset(&self, buf: &[u8]) -> Option<i32> {
+        Iso.utc_offset(&buf[Syslog5424::start(buf)?..])
+    }
+
+    fn field<'a>(&self, buf: &'a [u8], name: &str) -> Option<&'a [u8]> {
+        let (host, idx) = token(buf, self.prefix_span(buf).end + 1)?;
+        let (program, idx) = token(buf, idx)?;
+        let (pid, idx) = token(buf, idx)?;
+        let (msgid, _) = token(buf, idx)?;
+        let value = match name {
+            "host" => host,
+            "program" => program,
+            "pid" => pid,
+            "msgid" => msgid,
+            _ => return None,
+        };
+        if value == b"-" {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
@@ -1,10 +1,25 @@
 #[cfg(test)]
 mod test {
     use crate::process_file;
+    use crate::Options;
     use crate::DateTimeHolder;
+    use crate::FormatRegistry;
+    use crate::{MICROS_BITS, MICROS_MASK};
+    use crate::zone::{DstPolicy, Zone};
+    use chrono::{Local, TimeZone};
+    use std::str::FromStr;
+    use std::time::SystemTime;
+    use crate::archive::Archive;
+    use crate::decode::Compression;
+    use crate::files::{read_list, FileSelection};
+    use crate::locate::Locator;
+    use crate::rotate::{self, Rotation};
+    use crate::sample::Sampler;
+    use crate::strict::{Rejection, RejectionReport};
+    use crate::format::{normalize_with, Carmen, CarmenErr, ChronoFormat, Clf, Epoch, InputState, Json, Iso, LogFormat, Syslog5424, YearStrategy, Yoda};
     use crate::normalized_datetime_naive;
     use crate::normalized_datetime;
-    use std::io::Cursor;
+    use std::io::{Cursor, Read, Write};
 
     #[test]
     fn test_math_century() {
@@ -20,8 +35,8 @@ mod test {
     fn test_date_time_holder() {
         let log_line = r#"2099-12-31 00:00:01,828"#.to_string().into_bytes();
         let log_datetime = normalized_datetime(&log_line);
-        let start_end_date: DateTimeHolder = DateTimeHolder::new(None, Some(&"31.12.99 0:0:1".to_string()));
-        assert_eq!(log_datetime.unwrap().date_value, start_end_date.end);
+        let start_end_date: DateTimeHolder = DateTimeHolder::with_options(None, Some(&"31.12.99 0:0:1".to_string()), None, DstPolicy::Auto, 2000);
+        assert_eq!(log_datetime.unwrap().date_value >> MICROS_BITS, start_end_date.end >> MICROS_BITS);
     }    
 
     #[test]
@@ -29,7 +44,7 @@ mod test {
         let log_line = r#"2023-01-26 09:32:28,828 INFO  [null,d7256a35f724f75f9083233230373335393931] [de.telekom.crm.rest.service.base.impl.ServiceStateContainerFilter] (default task-24) START SERVICE [/EmailVerificationResult/v1/business-partner/email/verification-result] Header: Accept:[application/json, application/*+json] Accept-Encoding:[gzip] Authorization:[Bearer }"#.to_string().into_bytes();
         let log_datetime = normalized_datetime(&log_line);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2224316754763804);
+        assert_eq!(log_datetime.unwrap().date_value, 142363981763093088);
     } 
     
     #[test]
@@ -93,7 +108,7 @@ mod test {
         let log_line = r#"30.12.22 00:22:52 M     0 FILE /users/cloud/user1/data/projects/Software/tfc_source/tfc_apps/src/tfcwebserviceprovider/tfcrpc.cpp:615 [CRequestSOAP] PID: 3825 ServiceCall HTMX:\rwv/services/ERP/IntangibleAsset/SIMReadServices/getSIMInfo7.getSIMInfo7 CorrelationId: a63b1b3d-59bb-4851-8c98-c655"#.to_string().into_bytes();
         let log_datetime = normalized_datetime(&log_line);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2223264554292788);
+        assert_eq!(log_datetime.unwrap().date_value, 142342501984894976);
     }
 
     #[test]
@@ -101,7 +116,7 @@ mod test {
         let log_line = r#"20230729111238;edeyl6;;TfcWebserviceProvider;1950;E;0;0 Nr: 2 Message: E_TechUnexpectedService: ErrorCount=0;WorstError=-1;SubsystemID=0;TextDBID=0;LocationNr=0;FileName=//users//cloud//user1//data//projects//SoftWare//ccb_source//ccr_apps//src//servicesimpl//resourcereadservices//impl//CCGetSIMProfileStatus2ServiceImplementation.cpp;LineNumber=174;ErrorNo=2;Text=E_ProfServiceFailed: Fehler 2 beim Aufruf von IRIS-Service GetProfileStatus: Die ICC-ID ist im eSIM-System nicht bekannt.\nIRIS Fehlerinfo: Unknown ICCID;TextID=0;Recommendation=0;Level=5;OutputChannel=0;ExceptionClassName=E_TechUnexpectedService;ProcessId=0;ThreadId=0;ChannelId=0; 0  /users/cloud/user1/data/projects/SoftWare/tfc_source/tfc_apps/src/tfcwebserviceprovider/tfcrpc.cpp 398 TfcRpc 0;B2164F67-1BCF-4E57-BC58-6A17B74CA8CD"#.to_string().into_bytes();
         let log_datetime = normalized_datetime(&log_line);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2224342575025190);
+        assert_eq!(log_datetime.unwrap().date_value, 142390789616435200);
     }
 
     #[test]
@@ -109,7 +124,7 @@ mod test {
         let log_line = r#"20230729111238;"#.to_string().into_bytes();
         let log_datetime = normalized_datetime(&log_line);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2224342575025190);
+        assert_eq!(log_datetime.unwrap().date_value, 142390789616435200);
     }
 
     #[test]
@@ -117,7 +132,7 @@ mod test {
         let log_line = r#"2023-01-26 09:32:28"#.to_string().into_bytes();
         let log_datetime = normalized_datetime(&log_line);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2224316754763804);
+        assert_eq!(log_datetime.unwrap().date_value, 142363981762265088);
     }
 
     #[test]
@@ -128,7 +143,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"1.1.20 0:0:0".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -139,8 +154,8 @@ mod test {
         
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"30.12.22 02:30:57".to_string()), Some(&"31.12.22 0:0:0".to_string()));
-        assert_eq!(start_end_date.end, 2223264571064320);
-        process_file(&start_end_date, None, 0, false, false, &mut out, &mut data);
+        assert_eq!(start_end_date.end, 142342637893927487);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(false), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -151,8 +166,8 @@ mod test {
         
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"30.12.22 02:30:57".to_string()), Some(&"31.12.22 0:0:0".to_string()));
-        assert_eq!(start_end_date.end, 2223264571064320);
-        process_file(&start_end_date, None, 0, true, false, &mut out, &mut data);
+        assert_eq!(start_end_date.end, 142342637893927487);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -163,7 +178,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(None, Some(&"31.12.99 0:0:0".to_string()));
         assert_eq!(start_end_date.start, 0);
-        process_file(&start_end_date, None, 0, true, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -174,8 +189,8 @@ mod test {
         
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(None, Some(&"31.12.22 0:0:0".to_string()));
-        assert_eq!(start_end_date.end, 2223264571064320);
-        process_file(&start_end_date, None, 0, true, false, &mut out, &mut data);
+        assert_eq!(start_end_date.end, 142342637893927487);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -187,7 +202,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"1.1.23 0:0:0".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert!(out.is_empty());
     }
 
@@ -199,7 +214,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"14.01.2023 13:57:30".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
     }
 
     #[test]
@@ -210,7 +225,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:31".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -222,7 +237,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:31".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,false, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(false), &Options::default(), &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -234,7 +249,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:32".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, false, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut data);
         assert!(out.is_empty());
     }
 
@@ -246,7 +261,7 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:31".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, true, &mut out, &mut data);
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options { replace: true, ..Options::default() }, &mut out, &mut data);
         assert_eq!(data.into_inner(), out);
     }
 
@@ -258,7 +273,9 @@ mod test {
         let mut out: Vec<u8> = Vec::new();
         let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:31".to_string()), None);
         assert_eq!(start_end_date.end, u64::MAX,  " {} and {}", start_end_date.end, u64::MAX);
-        process_file(&start_end_date, None, 0,true, true, &mut out, &mut data);
+        let mut formats = FormatRegistry::builtin(true);
+        formats.set_pivot_year(2000);
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut data);
         assert_ne!(data.into_inner(), out);
         let log_line = r#"2099-12-30 02:30:57 M "#.to_string().into_bytes();
         let data = Cursor::new(log_line);
@@ -275,7 +292,7 @@ mod test {
         };
         let log_datetime = normalized_datetime_naive(v);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2223264554292788);
+        assert_eq!(log_datetime.unwrap().date_value, 142342501984894976);
     }
     
     #[test]
@@ -288,7 +305,7 @@ mod test {
         };
         let log_datetime = normalized_datetime_naive(v);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2223264453626395);
+        assert_eq!(log_datetime.unwrap().date_value, 142341676520253376);
     }
 
     #[test]
@@ -301,7 +318,7 @@ mod test {
         };
         let log_datetime = normalized_datetime_naive(v);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2224316754763804);
+        assert_eq!(log_datetime.unwrap().date_value, 142363981762265088);
     }
 
     #[test]
@@ -314,6 +331,1016 @@ mod test {
         };
         let log_datetime = normalized_datetime_naive(v);
         assert!(log_datetime.is_some());
-        assert_eq!(log_datetime.unwrap().date_value, 2224342575025190);
+        assert_eq!(log_datetime.unwrap().date_value, 142390789616435200);
+    }
+
+    #[test]
+    fn test_registry_span() {
+        let formats = FormatRegistry::builtin(true);
+        let log_datetime = formats.normalize(b"20230729111238;edeyl6;;TfcWebserviceProvider;1950;E");
+        assert!(log_datetime.is_some());
+        let log_datetime = log_datetime.unwrap();
+        assert_eq!(log_datetime.date_value, 142390789616435200);
+        assert_eq!(log_datetime.span, 0..14);
+        // only replace asks for the span
+        let mut state = formats.input_state(None);
+        state.spans = false;
+        assert_eq!(formats.normalize_in(b"20230729111238;edeyl6;;TfcWebserviceProvider;1950;E", &mut state).unwrap().span, 0..0);
+        let fixed = ChronoFormat::new("slash", "%Y/%m/%d %H:%M:%S", 19);
+        assert_eq!(fixed.prefix_span(b"2023/01/26 09:32:28 custom"), 0..19);
+    }
+
+    #[test]
+    fn test_registry_custom_format() {
+        let mut formats = FormatRegistry::new();
+        assert!(formats.normalize(b"2023/01/26 09:32:28 custom").is_none());
+        formats.register(Box::new(ChronoFormat::new("slash", "%Y/%m/%d %H:%M:%S", 19)));
+        let log_datetime = formats.normalize(b"2023/01/26 09:32:28 custom");
+        assert_eq!(log_datetime.unwrap().date_value, 142363981762265088);
+    }
+
+    #[test]
+    fn test_render_builtin() {
+        let mut out: Vec<u8> = Vec::new();
+        Carmen.render(142342501984894976, &mut out).unwrap();
+        assert_eq!(out, b"30.12.22 00:22:52");
+        out.clear();
+        CarmenErr.render(142390789616435200, &mut out).unwrap();
+        assert_eq!(out, b"20230729111238");
+        out.clear();
+        Yoda.render(142363981762265088, &mut out).unwrap();
+        assert_eq!(out, b"2023-01-26 09:32:28");
+    }
+
+    #[test]
+    fn test_user_format_offset() {
+        let format = ChronoFormat::from_spec("6@%d/%m/%Y %H:%M:%S").unwrap();
+        let log_line = b"host1 26/01/2023 09:32:28 hello";
+        let log_datetime = normalize_with(&format, log_line).unwrap();
+        assert_eq!(log_datetime.date_value, 142363981762265088);
+        assert_eq!(log_datetime.span, 6..25);
+        assert!(ChronoFormat::from_spec("%Q").is_err());
+    }
+
+    #[test]
+    fn test_user_format_replace() {
+        let log_line = "host1 26/01/2023 09:32:28 hello\nhost1 26/01/2023 10:32:28 late\n".to_string().into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::new();
+        formats.register(Box::new(ChronoFormat::from_spec("6@%d/%m/%Y %H:%M:%S").unwrap()));
+        let start_end_date: DateTimeHolder = DateTimeHolder::new(None, Some(&"26.01.2023 10:00:00".to_string()));
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut data);
+        assert_eq!(out, b"host1 2023-01-26 09:32:28 hello\n");
+    }
+
+    #[test]
+    fn test_parse_date_millis_window() {
+        let log_line = "2023-01-24 13:57:31,100 a\n2023-01-24 13:57:31,500 b\n24.12.22 00:10:27.999999 c\n2023-01-24 13:57:31.900 d\n".to_string().into_bytes();
+        for fast in [true, false] {
+            let mut data = Cursor::new(log_line.clone());
+            let mut out: Vec<u8> = Vec::new();
+            let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:31,200".to_string()), Some(&"24.01.2023 13:57:31.9".to_string()));
+            process_file(&start_end_date, None, &FormatRegistry::builtin(fast), &Options::default(), &mut out, &mut data);
+            assert_eq!(out, b"2023-01-24 13:57:31,500 b\n2023-01-24 13:57:31.900 d\n");
+        }
+    }
+
+    #[test]
+    fn test_end_date_whole_second() {
+        let log_datetime = normalized_datetime(b"2023-01-24 13:57:31.999999 end of second");
+        let start_end_date: DateTimeHolder = DateTimeHolder::new(None, Some(&"24.01.2023 13:57:31".to_string()));
+        assert_eq!(log_datetime.unwrap().date_value, start_end_date.end);
+        let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"24.01.2023 13:57:31".to_string()), None);
+        assert_eq!(start_end_date.start & MICROS_MASK, 0);
+    }
+
+    #[test]
+    fn test_replace_keeps_fraction() {
+        let log_line = "24.12.22 00:10:27,654 carmen\nhost1 26/01/2023 09:32:28.828123 user\n".to_string().into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::new();
+        formats.register(Box::new(ChronoFormat::from_spec("6@%d/%m/%Y %H:%M:%S%.f").unwrap()));
+        formats.append(FormatRegistry::builtin(true));
+        let start_end_date: DateTimeHolder = DateTimeHolder::new(Some(&"1.1.2000 0:0:0".to_string()), None);
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut data);
+        assert_eq!(out, b"2022-12-24 00:10:27,654 carmen\nhost1 2023-01-26 09:32:28.828123 user\n");
+    }
+
+    #[test]
+    fn test_zone_fall_back_auto() {
+        let log_line = "29.10.23 02:30:00 first\n29.10.23 02:59:59 b\n29.10.23 02:00:01 second\n29.10.23 02:30:00 second\n".to_string().into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::builtin(true);
+        assert_eq!(formats.set_zone("carmen", &Zone::from_str("Europe/Berlin").unwrap()), 1);
+        let utc = Zone::from_str("UTC").unwrap();
+        let start_end_date = DateTimeHolder::with_zone(Some(&"29.10.2023 01:20:00".to_string()), Some(&"29.10.2023 01:40:00".to_string()), Some(&utc), DstPolicy::Auto);
+        process_file(&start_end_date, None, &formats, &Options::default(), &mut out, &mut data);
+        assert_eq!(out, b"29.10.23 02:30:00 second\n");
+    }
+
+    #[test]
+    fn test_zone_dst_policy() {
+        let berlin = Zone::from_str("Europe/Berlin").unwrap();
+        let local = normalized_datetime(b"2023-10-29 02:30:00 ambiguous").unwrap().date_value;
+        let earliest = berlin.to_utc(local, DstPolicy::Earliest, 0).unwrap();
+        let latest = berlin.to_utc(local, DstPolicy::Latest, 0).unwrap();
+        assert_eq!(earliest, normalized_datetime(b"2023-10-29 00:30:00").unwrap().date_value);
+        assert_eq!(latest, normalized_datetime(b"2023-10-29 01:30:00").unwrap().date_value);
+        assert!(berlin.to_utc(local, DstPolicy::Reject, 0).is_none());
+        // spring forward, 02:30 doesn't exist
+        let local = normalized_datetime(b"2023-03-26 02:30:00 missing").unwrap().date_value;
+        assert!(berlin.to_utc(local, DstPolicy::Reject, 0).is_none());
+        let shifted = berlin.to_utc(local, DstPolicy::Earliest, 0).unwrap();
+        assert_eq!(shifted, normalized_datetime(b"2023-03-26 01:30:00").unwrap().date_value);
+        let fixed = Zone::from_str("+05:30").unwrap();
+        let local = normalized_datetime(b"2023-03-26 12:00:00").unwrap().date_value;
+        assert_eq!(fixed.to_utc(local, DstPolicy::Auto, 0).unwrap(), normalized_datetime(b"2023-03-26 06:30:00").unwrap().date_value);
+        assert!(Zone::from_str("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn test_normalized_datetime_iso() {
+        let log_datetime = normalized_datetime(b"2023-01-26T09:32:28.828+01:00 INFO started").unwrap();
+        assert_eq!(log_datetime.date_value, 142363981763093088);
+        assert_eq!(log_datetime.span, 0..29);
+        assert_eq!(Iso.utc_offset(b"2023-01-26T09:32:28.828+01:00 INFO"), Some(3600));
+        assert_eq!(Iso.utc_offset(b"2023-01-26T09:32:28-0530 INFO"), Some(-19800));
+        assert_eq!(Iso.utc_offset(b"2023-01-26T09:32:28Z INFO"), Some(0));
+        assert_eq!(Iso.utc_offset(b"2023-01-26T09:32:28 INFO"), None);
+        assert!(normalized_datetime(b"2023-01-26X09:32:28Z INFO").is_none());
+    }
+
+    #[test]
+    fn test_parse_date_iso_offset() {
+        let log_line = "2023-01-26T09:32:28.828+01:00 a\n2023-01-26T08:40:00Z b\n2023-01-26T11:00:00+01:00 c\n".to_string().into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::builtin(true);
+        formats.set_default_zone(&Zone::from_str("Europe/Berlin").unwrap());
+        let start_end_date = DateTimeHolder::with_zone(Some(&"2023-01-26T08:30:00Z".to_string()), Some(&"2023-01-26T10:00:00+01:00".to_string()), Some(&Zone::Local), DstPolicy::Auto);
+        process_file(&start_end_date, None, &formats, &Options::default(), &mut out, &mut data);
+        assert_eq!(out, b"2023-01-26T09:32:28.828+01:00 a\n2023-01-26T08:40:00Z b\n");
+    }
+
+    #[test]
+    fn test_syslog_year_rollover() {
+        let formats = FormatRegistry::builtin(true);
+        let mut state = InputState::new(None, YearStrategy::Start(2022));
+        let last = formats.normalize_in(b"Dec 31 23:59:58 web1 carmen[123]: last", &mut state).unwrap();
+        let first = formats.normalize_in(b"Jan  1 00:00:02 web1 carmen[123]: first", &mut state).unwrap();
+        assert_eq!(last.date_value, normalized_datetime(b"2022-12-31 23:59:58").unwrap().date_value);
+        assert_eq!(first.date_value, normalized_datetime(b"2023-01-01 00:00:02").unwrap().date_value);
+        assert_eq!(first.span, 0..15);
+        // december before the modification time in january belongs to the year before
+        let mtime = SystemTime::from(Local.with_ymd_and_hms(2023, 1, 5, 0, 0, 0).unwrap());
+        let mut state = InputState::new(Some(mtime), YearStrategy::Mtime);
+        let last = formats.normalize_in(b"<13>Dec 31 23:59:58 web1 carmen: last", &mut state).unwrap();
+        assert_eq!(last.date_value, normalized_datetime(b"2022-12-31 23:59:58").unwrap().date_value);
+        assert_eq!(last.span, 4..19);
+    }
+
+    #[test]
+    fn test_syslog_fields() {
+        let formats = FormatRegistry::builtin(true);
+        let line = b"Jan 26 09:32:28 web1 carmen[123]: started";
+        assert_eq!(formats.field(line, "host"), Some(&b"web1"[..]));
+        assert_eq!(formats.field(line, "program"), Some(&b"carmen"[..]));
+        assert_eq!(formats.field(line, "pid"), Some(&b"123"[..]));
+        let line = b"<34>1 2023-01-26T09:32:28.828Z web1 yoda 4711 - - started";
+        let log_datetime = formats.normalize(line).unwrap();
+        // without zones the offset is converted to the local wall clock
+        assert_eq!(Some(log_datetime.date_value), Zone::Local.from_utc(142363981763093088));
+        assert_eq!(log_datetime.span, 6..30);
+        assert_eq!(Syslog5424.utc_offset(line), Some(0));
+        assert_eq!(formats.field(line, "program"), Some(&b"yoda"[..]));
+        assert_eq!(formats.field(line, "pid"), Some(&b"4711"[..]));
+        assert_eq!(formats.field(line, "msgid"), None);
+    }
+
+    #[test]
+    fn test_clf() {
+        let line = b"127.0.0.1 - frank [26/Jan/2023:09:32:28 +0100] \"GET /yoda HTTP/1.1\" 200 2326";
+        assert!(Clf.detect(line));
+        assert_eq!(Clf.parse(line), Some(142363981762265088));
+        assert_eq!(Clf.prefix_span(line), 19..45);
+        assert_eq!(Clf.utc_offset(line), Some(3600));
+        assert_eq!(Clf.utc_offset(b"10.0.0.1 - - [26/Jan/2023:09:32:28 -0530] \"GET / HTTP/1.1\" 200 1"), Some(-19800));
+        let mut out: Vec<u8> = Vec::new();
+        Clf.render(142363981762265088, &mut out).unwrap();
+        assert_eq!(out, b"26/Jan/2023:09:32:28");
+        assert!(!Clf.detect(b"127.0.0.1 - - [26/Jan/2023:09:32:28] \"GET / HTTP/1.1\" 200 1"));
+        assert!(Clf.parse(b"127.0.0.1 - - [26/Foo/2023:09:32:28 +0100] \"GET / HTTP/1.1\" 200 1").is_none());
+    }
+
+    #[test]
+    fn test_parse_date_clf_with_yoda() {
+        let log_line = "2023-01-26 09:30:00,000 INFO yoda\n\
+            10.0.0.1 - - [26/Jan/2023:08:31:00 +0000] \"GET / HTTP/1.1\" 200 1\n\
+            10.0.0.1 - - [26/Jan/2023:08:20:00 +0000] \"GET / HTTP/1.1\" 200 1\n\
+            10.0.0.1 - - [26/Jan/2023:09:32:28 +0100] \"GET / HTTP/1.1\" 200 1\n"
+            .to_string()
+            .into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::builtin(true);
+        formats.set_default_zone(&Zone::from_str("Europe/Berlin").unwrap());
+        let start_end_date = DateTimeHolder::with_zone(Some(&"26.01.2023 09:30:00".to_string()), Some(&"26.01.2023 09:40:00".to_string()), Some(&Zone::from_str("Europe/Berlin").unwrap()), DstPolicy::Auto);
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut data);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 08:30:00,000 INFO yoda\n\
+            10.0.0.1 - - [2023-01-26 08:31:00] \"GET / HTTP/1.1\" 200 1\n\
+            10.0.0.1 - - [2023-01-26 08:32:28] \"GET / HTTP/1.1\" 200 1\n"
+        );
+    }
+
+    #[test]
+    fn test_epoch() {
+        let seconds = Epoch.parse(b"1674725548 job done").unwrap();
+        assert_eq!(seconds, 142363981762265088);
+        assert_eq!(Epoch.parse(b"1674725548828 job done").unwrap(), seconds + 828_000);
+        assert_eq!(Epoch.parse(b"1674725548828123 job done").unwrap(), seconds + 828_123);
+        assert_eq!(Epoch.parse(b"1674725548.5 job done").unwrap(), seconds + 500_000);
+        assert_eq!(Epoch.prefix_span(b"1674725548.5 job done"), 0..12);
+        assert_eq!(Epoch.prefix_span(b"1674725548828 job done"), 0..13);
+        assert!(!Epoch.detect(b"16747255488 job done"));
+        assert!(!Epoch.detect(b"20230126093228 carmen-err"));
+        // ids and counts: out of range or not followed by a blank
+        assert!(!Epoch.detect(b"4200000000 rows processed"));
+        assert!(!Epoch.detect(b"9999999999 bytes"));
+        assert!(!Epoch.detect(b"1674725548-7f3a order"));
+        assert!(Epoch.detect(b"1674725548;batch"));
+        assert!(Epoch.detect(b"1674725548"));
+        let mut out: Vec<u8> = Vec::new();
+        Epoch.render(seconds + 828_000, &mut out).unwrap();
+        assert_eq!(out, b"1674725548.828");
+    }
+
+    #[test]
+    fn test_parse_date_epoch() {
+        let log_line = "1674725540 a\n1674725548828 b\n1674725549 c\n".to_string().into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::builtin(true);
+        formats.register(Box::new(Epoch));
+        formats.set_default_zone(&Zone::Utc);
+        let start_end_date = DateTimeHolder::with_zone(Some(&"1674725548".to_string()), Some(&"1674725548".to_string()), Some(&Zone::Utc), DstPolicy::Auto);
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut data);
+        assert_eq!(out, b"2023-01-26 09:32:28.828 b\n");
+    }
+
+    #[test]
+    fn test_sampler() {
+        let mut formats = FormatRegistry::builtin(true);
+        formats.register(Box::new(Epoch));
+        let mut state = formats.input_state(None);
+        let mut sampler = Sampler::new(&formats, "test", 0);
+        for _ in 0..formats.sample_lines() {
+            assert!(sampler.normalize(b"2023-01-26 09:32:28,828 INFO yoda", &mut state).is_some());
+        }
+        assert_eq!(formats.formats()[sampler.dominant().unwrap()].name(), "yoda");
+        // payload with an epoch isn't taken for a timestamp
+        assert!(sampler.normalize(b"1674722048 rows loaded", &mut state).is_none());
+        assert!(formats.normalize(b"1674722048 rows loaded").is_some());
+        // repeated failures go back to detection
+        for _ in 0..20 {
+            sampler.normalize(b"1674722048 rows loaded", &mut state);
+        }
+        assert_eq!(sampler.dominant(), None);
+        assert!(sampler.normalize(b"1674722048 rows loaded", &mut state).is_some());
+    }
+
+    #[test]
+    fn test_sampler_stack_trace() {
+        let formats = FormatRegistry::builtin(true);
+        let mut state = formats.input_state(None);
+        let mut sampler = Sampler::new(&formats, "test", 0);
+        for _ in 0..formats.sample_lines() {
+            sampler.normalize(b"2023-01-26 09:32:28,828 INFO yoda", &mut state);
+        }
+        let dominant = sampler.dominant();
+        assert!(dominant.is_some());
+        // lines without timestamp aren't failures of the dominant format
+        for _ in 0..3 {
+            assert!(sampler.normalize(b"java.lang.IllegalStateException: yoda", &mut state).is_none());
+            for _ in 0..20 {
+                assert!(sampler.normalize(b"\tat yoda.Main.run(Main.java:42)", &mut state).is_none());
+            }
+            assert!(sampler.normalize(b"2023-01-26 09:32:29,828 INFO yoda", &mut state).is_some());
+        }
+        assert_eq!(sampler.dominant(), dominant);
+    }
+
+    #[test]
+    fn test_sampler_mixed() {
+        let mut formats = FormatRegistry::builtin(true);
+        formats.set_sample_lines(4);
+        let mut state = formats.input_state(None);
+        let mut sampler = Sampler::new(&formats, "test", 0);
+        sampler.normalize(b"2023-01-26 09:32:28,828 INFO yoda", &mut state);
+        sampler.normalize(b"26.01.23 09:32:28,828 INFO carmen", &mut state);
+        sampler.normalize(b"2023-01-26 09:32:28,828 INFO yoda", &mut state);
+        sampler.normalize(b"26.01.23 09:32:28,828 INFO carmen", &mut state);
+        assert_eq!(sampler.dominant(), None);
+        assert!(sampler.normalize(b"26.01.23 09:32:28,828 INFO carmen", &mut state).is_some());
+        assert!(sampler.normalize(b"2023-01-26 09:32:28,828 INFO yoda", &mut state).is_some());
+    }
+
+    #[test]
+    fn test_locator() {
+        let line = b"web1 4f2a9c 26.01.23 09:32:28,828 INFO carmen";
+        assert_eq!(Locator::Bytes(12).start(line), Some(12));
+        assert_eq!(Locator::Fields(2).start(line), Some(12));
+        assert_eq!(Locator::Fields(2).start(b"  web1\t4f2a9c  26.01.23"), Some(15));
+        assert_eq!(Locator::Fields(3).start(b"web1 4f2a9c"), None);
+        assert_eq!(Locator::regex(r"^\S+ \S+ (\d)").unwrap().start(line), Some(12));
+        assert_eq!(Locator::regex(r"\d\d\.\d\d\.").unwrap().start(line), Some(12));
+        assert!(Locator::regex("(").is_err());
+        let mut formats = FormatRegistry::builtin(true);
+        formats.set_locator(Locator::Fields(2));
+        let log_datetime = formats.normalize(line).unwrap();
+        assert_eq!(log_datetime.date_value, normalized_datetime(b"26.01.23 09:32:28,828").unwrap().date_value);
+        assert_eq!(log_datetime.span, 12..29);
+    }
+
+    #[test]
+    fn test_replace_with_locator() {
+        let log_line = "web1 4f2a9c 26.01.23 09:32:28,828 INFO carmen\nweb1 4f2a9c 26.01.23 10:32:28 INFO carmen\n".to_string().into_bytes();
+        let mut data = Cursor::new(log_line);
+        let mut out: Vec<u8> = Vec::new();
+        let mut formats = FormatRegistry::builtin(true);
+        formats.set_locator(Locator::regex(r"^\S+ \S+ (\d)").unwrap());
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), Some(&"26.01.2023 10:00:00".to_string()));
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut data);
+        assert_eq!(out, b"web1 4f2a9c 2023-01-26 09:32:28,828 INFO carmen\n");
+    }
+
+    #[test]
+    fn test_json() {
+        let json = Json::new(Vec::new(), true);
+        let line = br#"{"level":"info","@timestamp":"2023-01-26T09:32:28.828Z","msg":"a \"quoted\" {x}"}"#;
+        assert!(json.detect(line));
+        assert_eq!(json.parse(line), Some(142363981763093088));
+        assert_eq!(json.prefix_span(line), 30..54);
+        assert_eq!(json.utc_offset(line), Some(0));
+        assert!(!json.quote(line));
+        assert_eq!(json.field(line, "msg"), Some(&br#"a \"quoted\" {x}"#[..]));
+        let line = br#"{"msg":"started", "ts": 1674725548.828}"#;
+        assert_eq!(json.parse(line), Some(142363981763093088));
+        assert_eq!(json.prefix_span(line), 24..38);
+        assert!(json.quote(line));
+        let nested = Json::new(vec!["kubernetes.ts".to_string()], true);
+        let line = br#"{"log":{"ts":"x"},"kubernetes":{"pod":"yoda-1","ts":"2023-01-26 09:32:28,828"}}"#;
+        assert_eq!(nested.parse(line), Some(142363981763093088));
+        let dotted = br#"{"kubernetes.ts":"2023-01-26 09:32:28,828"}"#;
+        assert_eq!(nested.parse(dotted), Some(142363981763093088));
+        assert!(!json.detect(b"2023-01-26 09:32:28,828 INFO yoda"));
+        assert!(!json.detect(br#"{"level":"info","msg":"no time"}"#));
+    }
+
+    #[test]
+    fn test_parse_date_json() {
+        let log_line = "{\"ts\":\"2023-01-26 09:30:00,000\",\"msg\":\"a\"}\n\
+            {\"ts\":1674725548828, \"msg\":\"b\"}\n\
+            {\"ts\":\"2023-01-26 10:30:00,000\",\"msg\":\"c\"}\n"
+            .to_string()
+            .into_bytes();
+        let mut formats = FormatRegistry::new();
+        formats.register(Box::new(Json::new(Vec::new(), true)));
+        formats.set_default_zone(&Zone::Utc);
+        let start_end_date = DateTimeHolder::with_zone(Some(&"26.01.2023 09:31:00".to_string()), Some(&"26.01.2023 10:00:00".to_string()), Some(&Zone::Utc), DstPolicy::Auto);
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, None, &formats, &Options::default(), &mut out, &mut Cursor::new(log_line.clone()));
+        assert_eq!(out, b"{\"ts\":1674725548828, \"msg\":\"b\"}\n");
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, None, &formats, &Options { replace: true, ..Options::default() }, &mut out, &mut Cursor::new(log_line));
+        assert_eq!(out, b"{\"ts\":\"2023-01-26 09:32:28.828\", \"msg\":\"b\"}\n");
+    }
+
+    #[test]
+    fn test_pivot_year() {
+        let carmen_99 = normalized_datetime(b"24.12.99 00:10:27,6 INFO").unwrap().date_value;
+        assert_eq!(carmen_99 >> 46, 1999);
+        assert_eq!(normalized_datetime(b"24.12.22 00:10:27,6 INFO").unwrap().date_value >> 46, 2022);
+        for fast in [true, false] {
+            let mut formats = FormatRegistry::builtin(fast);
+            formats.set_pivot_year(2000);
+            let mut state = formats.input_state(None);
+            assert_eq!(formats.normalize_in(b"24.12.99 00:10:27,6 INFO", &mut state).unwrap().date_value >> 46, 2099);
+            formats.set_pivot_year(1950);
+            let mut state = formats.input_state(None);
+            assert_eq!(formats.normalize_in(b"24.12.49 00:10:27,6 INFO", &mut state).unwrap().date_value >> 46, 2049);
+            assert_eq!(formats.normalize_in(b"24.12.50 00:10:27,6 INFO", &mut state).unwrap().date_value >> 46, 1950);
+        }
+        let bounds = DateTimeHolder::with_options(Some(&"24.12.99 00:00:00".to_string()), None, None, DstPolicy::Auto, 2000);
+        assert_eq!(bounds.start >> 46, 2099);
+        let bounds = DateTimeHolder::new(Some(&"24.12.99 00:00:00".to_string()), None);
+        assert_eq!(bounds.start >> 46, 1999);
+    }
+
+    #[test]
+    fn test_strict_calendar() {
+        assert_eq!(Rejection::of(normalized_datetime(b"2023-02-31 10:00:00 x").unwrap().date_value), Some(Rejection::Day));
+        assert_eq!(Rejection::of(normalized_datetime(b"2023-02-29 10:00:00 x").unwrap().date_value), Some(Rejection::Day));
+        assert_eq!(Rejection::of(normalized_datetime(b"2024-02-29 10:00:00 x").unwrap().date_value), None);
+        assert_eq!(Rejection::of(normalized_datetime(b"00.01.23 10:00:00 x").unwrap().date_value), Some(Rejection::Day));
+        assert_eq!(Rejection::of(normalized_datetime(b"01.00.23 10:00:00 x").unwrap().date_value), Some(Rejection::Month));
+        assert_eq!(Rejection::of(normalized_datetime(b"20230101240000 x").unwrap().date_value), Some(Rejection::Hour));
+        let lines: [&[u8]; 8] = [
+            b"2024-02-29 10:00:00,000 INFO leap",
+            b"2023-02-29 10:00:00,000 INFO no leap",
+            b"31.04.23 10:00:00 INFO carmen",
+            b"30.04.23 24:00:00 INFO carmen",
+            b"30.04.23 23:59:59 INFO carmen",
+            b"20230001120000 carmen-err",
+            b"20230101120000 carmen-err",
+            b"    at yoda.Main.run(Main.java:42)",
+        ];
+        let fast = FormatRegistry::builtin(true);
+        let chrono = FormatRegistry::builtin(false);
+        for line in lines {
+            let mut state = fast.input_state(None);
+            state.strict = true;
+            let fast_value = fast.normalize_in(line, &mut state).map(|value| value.date_value);
+            assert_eq!(fast_value, chrono.normalize(line).map(|value| value.date_value), "{}", String::from_utf8_lossy(line));
+            if fast_value.is_none() {
+                assert_eq!(fast.rejection(line), chrono.rejection(line));
+            }
+        }
+        assert_eq!(fast.rejection(lines[1]), Rejection::Day);
+        assert_eq!(fast.rejection(lines[3]), Rejection::Hour);
+        assert_eq!(fast.rejection(lines[5]), Rejection::Month);
+        assert_eq!(fast.rejection(lines[7]), Rejection::NoTimestamp);
+        // 1900 isn't a leap year, the fallback of chrono uses the pivot too
+        let line = b"29.02.00 24:00:00 INFO carmen";
+        let mut chrono = FormatRegistry::builtin(false);
+        assert_eq!(chrono.rejection(line), Rejection::Hour);
+        chrono.set_pivot_year(1900);
+        assert_eq!(chrono.rejection(line), Rejection::Day);
+        let mut fast = FormatRegistry::builtin(true);
+        fast.set_pivot_year(1900);
+        assert_eq!(fast.rejection(line), Rejection::Day);
+    }
+
+    #[test]
+    fn test_rejection_report() {
+        let mut report = RejectionReport::new();
+        for line in 1..=7 {
+            report.add(Rejection::Day, line);
+        }
+        report.add(Rejection::Hour, 9);
+        assert_eq!(report.count(Rejection::Day), 7);
+        assert_eq!(report.count(Rejection::Month), 0);
+        assert_eq!(report.total(), 8);
+    }
+
+    #[test]
+    fn test_records() {
+        let log_line = "2023-01-26 09:00:00,000 ERROR before\n\
+            java.lang.IllegalStateException: yoda\n\
+            \tat yoda.Main.run(Main.java:42)\n\
+            2023-01-26 09:32:28,828 ERROR inside\n\
+            java.lang.IllegalStateException: carmen\n\
+            \tat carmen.Main.run(Main.java:42)\n\
+            \tat carmen.Main.main(Main.java:7)\n\
+            \n\
+            2023-01-26 10:30:00,000 INFO after\n\
+            \tat yoda.Main.run(Main.java:42)\n"
+            .to_string()
+            .into_bytes();
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:30:00".to_string()), Some(&"26.01.2023 10:00:00".to_string()));
+        let formats = FormatRegistry::builtin(true);
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, None, &formats, &Options::default(), &mut out, &mut Cursor::new(log_line.clone()));
+        assert_eq!(out, b"2023-01-26 09:32:28,828 ERROR inside\n");
+        let mut out: Vec<u8> = Vec::new();
+        let options = Options { replace: true, record_lines: 1000, ..Options::default() };
+        process_file(&start_end_date, None, &formats, &options, &mut out, &mut Cursor::new(log_line.clone()));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 09:32:28,828 ERROR inside\n\
+            java.lang.IllegalStateException: carmen\n\
+            \tat carmen.Main.run(Main.java:42)\n\
+            \tat carmen.Main.main(Main.java:7)\n\
+            \n"
+        );
+        let mut out: Vec<u8> = Vec::new();
+        let options = Options { record_lines: 2, ..Options::default() };
+        process_file(&start_end_date, None, &formats, &options, &mut out, &mut Cursor::new(log_line));
+        assert_eq!(String::from_utf8(out).unwrap(), "2023-01-26 09:32:28,828 ERROR inside\njava.lang.IllegalStateException: carmen\n");
+    }
+
+    fn decode(compressed: Vec<u8>) -> String {
+        let mut content = String::new();
+        Compression::sniff(&compressed).decoder(Cursor::new(compressed)).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_compression() {
+        use std::io::Write;
+        let log = "2023-01-26 09:32:28,828 INFO yoda\n";
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(log.as_bytes()).unwrap();
+        assert_eq!(decode(gz.finish().unwrap()), log);
+        #[cfg(feature = "zstd")]
+        assert_eq!(decode(zstd::encode_all(log.as_bytes(), 3).unwrap()), log);
+        #[cfg(feature = "xz")]
+        {
+            let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+            xz.write_all(log.as_bytes()).unwrap();
+            assert_eq!(decode(xz.finish().unwrap()), log);
+        }
+        #[cfg(feature = "bzip2")]
+        {
+            let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            bz.write_all(log.as_bytes()).unwrap();
+            assert_eq!(decode(bz.finish().unwrap()), log);
+        }
+        #[cfg(feature = "lz4")]
+        {
+            let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            lz4.write_all(log.as_bytes()).unwrap();
+            assert_eq!(decode(lz4.finish().unwrap()), log);
+        }
+        #[cfg(not(feature = "zstd"))]
+        assert!(Compression::Zstd.decoder(Cursor::new(Vec::new())).is_err());
+    }
+
+    fn gzip(content: impl AsRef<[u8]>) -> Vec<u8> {
+        use std::io::Write;
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(content.as_ref()).unwrap();
+        gz.finish().unwrap()
+    }
+
+    #[test]
+    fn test_sniff_compression() {
+        assert_eq!(Compression::sniff(&gzip("")), Compression::Gzip);
+        assert_eq!(Compression::sniff(b"BZh91AY&SY"), Compression::Bzip2);
+        assert_eq!(Compression::sniff(&[0x28, 0xb5, 0x2f, 0xfd, 0]), Compression::Zstd);
+        assert_eq!(Compression::sniff(&[0xfd, b'7', b'z', b'X', b'Z', 0, 0]), Compression::Xz);
+        assert_eq!(Compression::sniff(&[0x04, 0x22, 0x4d, 0x18]), Compression::Lz4);
+        assert_eq!(Compression::sniff(b"2023-01-26 09:32:28,828"), Compression::None);
+        assert_eq!(Compression::sniff(b""), Compression::None);
+    }
+
+    #[test]
+    fn test_multi_member_gzip() {
+        // logrotate copytruncate + append: two gzip members in one file, without .gz
+        let mut content = gzip("2023-01-26 09:32:28,828 INFO first\n");
+        content.extend(gzip("2023-01-26 09:33:28,828 INFO second\n"));
+        let path = std::env::temp_dir().join(format!("fd_multi_member_{}.log", std::process::id()));
+        std::fs::write(&path, &content).unwrap();
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), None);
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, path.to_str(), &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut std::io::stdin());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out, b"2023-01-26 09:32:28,828 INFO first\n2023-01-26 09:33:28,828 INFO second\n");
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut Cursor::new(content));
+        assert_eq!(out, b"2023-01-26 09:32:28,828 INFO first\n2023-01-26 09:33:28,828 INFO second\n");
+    }
+
+    #[test]
+    fn test_truncated_gzip() {
+        let mut content = gzip("2023-01-26 09:32:28,828 INFO first\n2023-01-26 09:33:28,828 INFO second\n");
+        // cut in the trailer, the content is complete
+        content.truncate(content.len() - 4);
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), None);
+        let mut out: Vec<u8> = Vec::new();
+        let corruption = process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut Cursor::new(content.clone()));
+        let corruption = corruption.unwrap();
+        assert_eq!(corruption.consumed, content.len() as u64);
+        assert_eq!(corruption.decoded, 71);
+        assert_eq!(out, b"2023-01-26 09:32:28,828 INFO first\n2023-01-26 09:33:28,828 INFO second\n");
+        let mut out: Vec<u8> = Vec::new();
+        let complete = gzip("2023-01-26 09:32:28,828 INFO first\n");
+        assert!(process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut Cursor::new(complete)).is_none());
+    }
+
+    // tar with the given members, the .gz ones are compressed
+    fn tar(members: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in members {
+            let content = if name.ends_with(".gz") { gzip(content) } else { content.as_bytes().to_vec() };
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(1674722048);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_slice()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_sniff_archive() {
+        assert_eq!(Archive::sniff(&tar(&[("a.log", "")])), Some(Archive::Tar));
+        assert_eq!(Archive::sniff(b"PK\x03\x04\x14\x00"), Some(Archive::Zip));
+        assert_eq!(Archive::sniff(b"2023-01-26 09:32:28,828"), None);
+        assert_eq!(Archive::sniff(b""), None);
+    }
+
+    #[test]
+    fn test_tar_gz_members() {
+        let archive = gzip(tar(&[
+            ("logs/carmen.log", "26.01.23 09:32:28,828 INFO carmen\n"),
+            ("logs/yoda.log.1.gz", "2023-01-26 09:33:28,828 INFO yoda\n\tat continued\n"),
+            ("logs/old.log", "2023-01-25 09:33:28,828 INFO old\n"),
+        ]));
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), None);
+        let formats = FormatRegistry::builtin(true);
+        let options = Options { record_lines: 10, ..Options::default() };
+        let mut out: Vec<u8> = Vec::new();
+        let corruption = process_file(&start_end_date, None, &formats, &options, &mut out, &mut Cursor::new(archive.clone()));
+        assert!(corruption.is_none());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "stdin!logs/carmen.log:26.01.23 09:32:28,828 INFO carmen\n\
+             stdin!logs/yoda.log.1.gz:2023-01-26 09:33:28,828 INFO yoda\n\
+             stdin!logs/yoda.log.1.gz:\tat continued\n"
+        );
+        let mut members = globset::GlobSetBuilder::new();
+        members.add(globset::Glob::new("*yoda*").unwrap());
+        let options = Options { replace: true, members: Some(members.build().unwrap()), ..options };
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, None, &formats, &options, &mut out, &mut Cursor::new(archive));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "stdin!logs/yoda.log.1.gz:2023-01-26 09:33:28,828 INFO yoda\nstdin!logs/yoda.log.1.gz:\tat continued\n"
+        );
+    }
+
+    #[test]
+    fn test_zip_members() {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.add_directory("logs/", options).unwrap();
+        zip.start_file("logs/carmen.log", options).unwrap();
+        zip.write_all(b"26.01.23 09:32:28,828 INFO carmen\n").unwrap();
+        zip.start_file("logs/yoda.log.gz", options).unwrap();
+        zip.write_all(&gzip("2023-01-26 09:33:28,828 INFO yoda\n")).unwrap();
+        let path = std::env::temp_dir().join(format!("fd_members_{}.zip", std::process::id()));
+        std::fs::write(&path, zip.finish().unwrap().into_inner()).unwrap();
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), None);
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, path.to_str(), &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut std::io::stdin());
+        let name = path.to_str().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{name}!logs/carmen.log:26.01.23 09:32:28,828 INFO carmen\n{name}!logs/yoda.log.gz:2023-01-26 09:33:28,828 INFO yoda\n")
+        );
+    }
+
+    #[test]
+    fn test_file_selection() {
+        let dir = std::env::temp_dir().join(format!("fd_selection_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in ["b.log", "a.log.1.gz", "notes.txt", "sub/c.log", "archive/d.log"] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let glob = |pattern: &str| {
+            let mut globs = globset::GlobSetBuilder::new();
+            globs.add(globset::Glob::new(pattern).unwrap());
+            Some(globs.build().unwrap())
+        };
+        let name = |path: &str| dir.join(path).to_string_lossy().into_owned();
+        let args = vec![dir.to_string_lossy().into_owned(), "explicit.txt".to_string()];
+        assert_eq!(
+            FileSelection::new(glob("*.log*"), glob("*/archive")).expand(&args),
+            vec![name("a.log.1.gz"), name("b.log"), name("sub/c.log"), "explicit.txt".to_string()]
+        );
+        assert_eq!(FileSelection::default().expand(&args).len(), 6);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_list() {
+        assert_eq!(read_list(&b"a.log\r\nb dir/c.log\n\n"[..], false).unwrap(), vec!["a.log", "b dir/c.log"]);
+        assert_eq!(read_list(&b"a.log\0new\nline.log\0"[..], true).unwrap(), vec!["a.log", "new\nline.log"]);
+    }
+
+    #[test]
+    fn test_rotation() {
+        assert_eq!(Rotation::of("/var/log/server.log"), ("/var/log/server.log", Rotation::Current));
+        assert_eq!(Rotation::of("/var/log/server.log.1"), ("/var/log/server.log", Rotation::Numbered(1)));
+        assert_eq!(Rotation::of("server.log.12.gz"), ("server.log", Rotation::Numbered(12)));
+        assert_eq!(Rotation::of("server.log-20230126.gz"), ("server.log", Rotation::Dated(20230126000000)));
+        assert_eq!(Rotation::of("server.log.2023-01-26"), ("server.log", Rotation::Dated(20230126000000)));
+        assert_eq!(Rotation::of("server.log-2023012613"), ("server.log", Rotation::Dated(20230126130000)));
+        let files = ["server.log", "other.log.1", "server.log.1", "server.log.10.gz", "server.log.2.gz", "other.log"];
+        assert_eq!(
+            rotate::order(files.iter().map(|file| file.to_string()).collect()),
+            vec!["server.log.10.gz", "server.log.2.gz", "server.log.1", "server.log", "other.log.1", "other.log"]
+        );
+        let files = ["app.log", "app.log-20230126.gz", "app.log-20230125.gz"];
+        assert_eq!(
+            rotate::order(files.iter().map(|file| file.to_string()).collect()),
+            vec!["app.log-20230125.gz", "app.log-20230126.gz", "app.log"]
+        );
+    }
+
+    #[test]
+    fn test_skip_outside() {
+        let dir = std::env::temp_dir().join(format!("fd_rotated_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let name = |file: &str| dir.join(file).to_string_lossy().into_owned();
+        std::fs::write(name("server.log.3.gz"), gzip("2023-01-24 09:00:00,000 INFO three\n")).unwrap();
+        std::fs::write(name("server.log.2.gz"), gzip("2023-01-25 09:00:00,000 INFO two\n")).unwrap();
+        std::fs::write(name("server.log.1"), "2023-01-26 09:00:00,000 INFO one\n2023-01-26 23:00:00,000 INFO one\n").unwrap();
+        std::fs::write(name("server.log"), "2023-01-27 09:00:00,000 INFO current\n").unwrap();
+        let files = rotate::order(vec![name("server.log"), name("server.log.1"), name("server.log.2.gz"), name("server.log.3.gz")]);
+        let formats = FormatRegistry::builtin(true);
+        assert_eq!(rotate::first_timestamp(&files[0], &formats), normalized_datetime(b"2023-01-24 09:00:00,000").map(|value| value.date_value));
+        assert_eq!(rotate::last_timestamp(&files[0], &formats), None);
+        let start_end_date = DateTimeHolder::new(Some(&"25.01.2023 12:00:00".to_string()), Some(&"26.01.2023 12:00:00".to_string()));
+        assert_eq!(rotate::skip_outside(files, &start_end_date, &formats, 0), vec![name("server.log.2.gz"), name("server.log.1")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // yoda lines one second apart from 26.01.2023 00:00:00, with a continuation line each
+    fn sorted_log(lines: u32) -> String {
+        (0..lines)
+            .map(|second| {
+                let time = chrono::NaiveDate::from_ymd_opt(2023, 1, 26).unwrap().and_hms_opt(0, 0, 0).unwrap() + chrono::Duration::seconds(second as i64);
+                format!("{},000 INFO line {}\n\tat continued\n", time.format("%Y-%m-%d %H:%M:%S"), second)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_seek_start() {
+        let path = std::env::temp_dir().join(format!("fd_seek_{}.log", std::process::id()));
+        std::fs::write(&path, sorted_log(20_000)).unwrap();
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 05:00:00".to_string()), Some(&"26.01.2023 05:00:01".to_string()));
+        let formats = FormatRegistry::builtin(true);
+        let offset = crate::seek::start_offset(&mut std::fs::File::open(&path).unwrap(), start_end_date.start, &formats, None).unwrap();
+        let target = sorted_log(18_000).len() as u64;
+        assert!(offset > 0 && offset <= target && target - offset <= 65_536 + 4096, "{offset}");
+        let options = Options { record_lines: 2, seek: true, ..Options::default() };
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, path.to_str(), &formats, &options, &mut out, &mut std::io::stdin());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 05:00:00,000 INFO line 18000\n\tat continued\n2023-01-26 05:00:01,000 INFO line 18001\n\tat continued\n"
+        );
+        // concatenated files aren't sorted, read from the start
+        std::fs::write(&path, sorted_log(10_000) + &sorted_log(20_000)).unwrap();
+        assert_eq!(crate::seek::start_offset(&mut std::fs::File::open(&path).unwrap(), start_end_date.start, &formats, None).unwrap(), 0);
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, path.to_str(), &formats, &options, &mut out, &mut std::io::stdin());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 05:00:00,000 INFO line 18000\n\tat continued\n2023-01-26 05:00:01,000 INFO line 18001\n\tat continued\n"
+        );
+    }
+
+    #[cfg(feature = "index")]
+    #[test]
+    fn test_gzip_index() {
+        use crate::index::{Index, IndexedReader};
+        let content = sorted_log(20_000);
+        let (first, second) = content.split_at(content.len() / 2 + 5);
+        // two members, the second starts within a line
+        let mut compressed = gzip(first);
+        compressed.extend(gzip(second));
+        let path = std::env::temp_dir().join(format!("fd_index_{}.log.gz", std::process::id()));
+        let name = path.to_str().unwrap();
+        std::fs::write(&path, &compressed).unwrap();
+        let formats = FormatRegistry::builtin(true);
+        let index = Index::build(name, &formats, 16_384).unwrap();
+        assert!(index.checkpoints.len() > 2);
+        index.write(&Index::sidecar(name)).unwrap();
+        let read = Index::read(&Index::sidecar(name)).unwrap();
+        assert_eq!(read.checkpoints.len(), index.checkpoints.len());
+        assert_eq!(read.settings, formats.settings());
+        for checkpoint in read.checkpoints.iter().skip(1) {
+            // the content from the line behind the checkpoint on
+            let mut file = std::fs::File::open(&path).unwrap();
+            std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(checkpoint.input - (checkpoint.bits > 0) as u64)).unwrap();
+            let mut uncompressed = String::new();
+            IndexedReader::new(file, checkpoint).unwrap().read_to_string(&mut uncompressed).unwrap();
+            let output = checkpoint.output as usize;
+            let line = output + content[output..].find('\n').unwrap() + 1;
+            assert_eq!(uncompressed, content[line..]);
+            let first_line = content[line..].lines().find(|line| line.starts_with("2023")).unwrap();
+            assert_eq!(checkpoint.first, normalized_datetime(first_line.as_bytes()).map(|value| value.date_value));
+        }
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 05:00:00".to_string()), Some(&"26.01.2023 05:00:01".to_string()));
+        let mut checkpoint_file = std::fs::File::open(&path).unwrap();
+        let checkpoint = crate::index::checkpoint(&mut checkpoint_file, name, start_end_date.start, &formats, 0).unwrap();
+        assert!(checkpoint.output > content.len() as u64 / 2);
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, Some(name), &formats, &Options::default(), &mut out, &mut std::io::stdin());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 05:00:00,000 INFO line 18000\n2023-01-26 05:00:01,000 INFO line 18001\n"
+        );
+        // the lines in another zone than the index, its local timestamps don't compare
+        let mut zoned = FormatRegistry::builtin(true);
+        zoned.set_default_zone(&Zone::from_str("-05:00").unwrap());
+        assert!(crate::index::checkpoint(&mut checkpoint_file, name, start_end_date.start, &zoned, 0).is_none());
+        let start_end_date = DateTimeHolder::with_zone(
+            Some(&"2023-01-26T10:00:00Z".to_string()),
+            Some(&"2023-01-26T10:00:01Z".to_string()),
+            Some(&Zone::Utc),
+            DstPolicy::Auto,
+        );
+        let mut out: Vec<u8> = Vec::new();
+        process_file(&start_end_date, Some(name), &zoned, &Options::default(), &mut out, &mut std::io::stdin());
+        std::fs::remove_file(Index::sidecar(name)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 05:00:00,000 INFO line 18000\n2023-01-26 05:00:01,000 INFO line 18001\n"
+        );
+    }
+
+    #[test]
+    fn test_relative_date() {
+        let now = crate::normalize_bits(chrono::Utc::now().naive_utc(), 1970);
+        let (five_minutes, offset) = crate::normalized_command_line_date(Some(&"now-5m".to_string()), false, 1970).unwrap().unwrap();
+        assert_eq!(offset, Some(0));
+        assert!(five_minutes < now);
+        assert!(crate::normalized_command_line_date(Some(&"now-300s".to_string()), false, 1970).unwrap().unwrap().0 >= five_minutes);
+        assert!(crate::normalized_command_line_date(Some(&"now".to_string()), false, 1970).unwrap().unwrap().0 >= now);
+        assert!(crate::command_line_date_has_offset("now-2h"));
+        for invalid in ["now-5", "now-1x", "now+5m", "now--5m", "nowadays", "now-99999999999999d"] {
+            assert!(crate::normalized_command_line_date(Some(&invalid.to_string()), false, 1970).unwrap().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_follow_file() {
+        let path = std::env::temp_dir().join(format!("fd_follow_{}.log", std::process::id()));
+        let rotated = path.with_extension("log.1");
+        std::fs::write(&path, "2023-01-26 09:00:00,000 INFO before\n2023-01-26 09:30:00,000 INFO first\n").unwrap();
+        let writer = {
+            let (path, rotated) = (path.clone(), rotated.clone());
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+                file.write_all(b"2023-01-26 09:31:00,000 INFO appended\n\tat continued\n").unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(600));
+                std::fs::rename(&path, &rotated).unwrap();
+                std::fs::write(&path, "2023-01-26 09:32:00,000 INFO rotated\n2023-01-26 11:00:00,000 INFO after\n2023-01-26 09:33:00,000 INFO unread\n").unwrap();
+            })
+        };
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:30:00".to_string()), Some(&"26.01.2023 10:00:00".to_string()));
+        let formats = FormatRegistry::builtin(true);
+        let options = Options { record_lines: 10, ..Options::default() };
+        let mut out: Vec<u8> = Vec::new();
+        // ends at the line behind the end date, not by the idle timeout
+        crate::follow_file(&start_end_date, path.to_str().unwrap(), &formats, &options, &mut out, Some(std::time::Duration::from_secs(5)));
+        writer.join().unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2023-01-26 09:30:00,000 INFO first\n2023-01-26 09:31:00,000 INFO appended\n\tat continued\n2023-01-26 09:32:00,000 INFO rotated\n"
+        );
+        // truncated and idle
+        let file = std::fs::File::open(&path).unwrap();
+        let mut tail = crate::follow::Tail::new(path.to_str().unwrap(), file, Some(std::time::Duration::from_millis(600)), 0).unwrap();
+        let mut content = String::new();
+        tail.read_to_string(&mut content).unwrap();
+        assert!(content.ends_with("unread\n"));
+        std::fs::write(&path, "short\n").unwrap();
+        content.clear();
+        tail.read_to_string(&mut content).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rotated).unwrap();
+        assert_eq!(content, "short\n");
+    }
+
+    #[test]
+    fn test_mapped_file() {
+        let path = std::env::temp_dir().join(format!("fd_mapped_{}.log", std::process::id()));
+        let content = sorted_log(20_000) + "2023-01-26 09:00:00,000 INFO crlf\r\n\tat continued\n2023-01-26 09:00:01,000 INFO no newline";
+        std::fs::write(&path, &content).unwrap();
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 05:00:00".to_string()), None);
+        let filter = |mmap: bool, seek: bool, replace: bool| {
+            let formats = FormatRegistry::builtin(true);
+            let options = Options { record_lines: 2, mmap, seek, replace, ..Options::default() };
+            let mut out: Vec<u8> = Vec::new();
+            process_file(&start_end_date, path.to_str(), &formats, &options, &mut out, &mut std::io::stdin());
+            String::from_utf8(out).unwrap()
+        };
+        let buffered = filter(false, false, false);
+        assert!(buffered.starts_with("2023-01-26 05:00:00,000 INFO line 18000\n\tat continued\n"));
+        assert!(buffered.ends_with("\r\n\tat continued\n2023-01-26 09:00:01,000 INFO no newline"));
+        assert_eq!(filter(true, false, false), buffered);
+        assert_eq!(filter(true, true, false), buffered);
+        assert_eq!(filter(true, false, true), filter(false, false, true));
+        // compressed files are read through a buffer
+        std::fs::write(&path, gzip(&content)).unwrap();
+        assert_eq!(filter(true, false, false), buffered);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_jobs_in_order() {
+        let files: Vec<String> = (0..12).map(|idx| format!("file{idx}")).collect();
+        let mut out: Vec<u8> = Vec::new();
+        let mut spilled = Vec::new();
+        // later files are done first, every third one is spilled
+        crate::jobs::in_order(
+            &files,
+            4,
+            |file, spool| {
+                let idx: usize = file[4..].parse().unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(5 * (12 - idx) as u64));
+                let size = if idx.is_multiple_of(3) { 1_500_000 } else { 10 };
+                spool.write_all(file.repeat(size / file.len()).as_bytes()).unwrap();
+                spool.write_all(b"\n").unwrap();
+                idx == 7
+            },
+            |file, spool, corrupt| {
+                assert_eq!(corrupt, file == "file7");
+                spilled.push(spool.spilled());
+                spool.copy_to(&mut out).unwrap();
+            },
+        );
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), files.len());
+        for (line, file) in lines.iter().zip(&files) {
+            assert!(line.starts_with(file.as_str()) && line.len() % file.len() == 0, "{file}");
+        }
+        assert_eq!(spilled, (0..12_usize).map(|idx| idx.is_multiple_of(3)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_file_order() {
+        use crate::jobs::FileOrder;
+        let dir = std::env::temp_dir().join(format!("fd_order_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, content: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let files = vec![
+            file("empty.log", ""),
+            file("late.log", "2023-01-26 12:00:00,000 INFO late\n"),
+            file("early.log", "no timestamp\n2023-01-26 08:00:00,000 INFO early\n"),
+        ];
+        assert!("size".parse::<FileOrder>().is_err());
+        let formats = FormatRegistry::builtin(true);
+        assert_eq!(FileOrder::Input.sort(files.clone(), &formats), files);
+        let sorted = "time".parse::<FileOrder>().unwrap().sort(files.clone(), &formats);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(sorted, vec![files[2].clone(), files[1].clone(), files[0].clone()]);
+    }
+
+    #[test]
+    fn test_epoch_records() {
+        let log_line = "2023-01-26 09:32:28,828 INFO start\n1674722048 is an id\n1234567890 rows processed\n2023-01-26 09:32:29,000 INFO done\n".to_string();
+        let filter = |formats: &FormatRegistry| {
+            let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), None);
+            let mut out: Vec<u8> = Vec::new();
+            let options = Options { record_lines: 10, ..Options::default() };
+            process_file(&start_end_date, None, formats, &options, &mut out, &mut Cursor::new(log_line.clone().into_bytes()));
+            String::from_utf8(out).unwrap()
+        };
+        // epoch isn't builtin, numbers in payload lines continue the record
+        let mut formats = FormatRegistry::builtin(true);
+        assert_eq!(filter(&formats), log_line);
+        assert!(formats.normalize(b"1674722048 is an id").is_none());
+        formats.register(Box::new(Epoch));
+        assert!(formats.normalize(b"1674722048 is an id").is_some());
+        assert!(formats.normalize(b"1234567890rows processed").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_member() {
+        let lines: String = (0..1000).map(|idx| format!("2023-01-26 09:32:28,828 INFO f1 line {idx}\n")).collect();
+        let truncated = gzip(&lines);
+        let truncated = &truncated[..truncated.len() / 2];
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [("f1.log.gz", truncated), ("f2.log", b"2023-01-26 09:33:28,828 INFO f2\n".as_slice()), ("f3.log", b"2023-01-26 09:34:28,828 INFO f3\n".as_slice())] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
+        }
+        let archive = builder.into_inner().unwrap();
+        let start_end_date = DateTimeHolder::new(Some(&"26.01.2023 09:00:00".to_string()), None);
+        let mut out: Vec<u8> = Vec::new();
+        let corruption = process_file(&start_end_date, None, &FormatRegistry::builtin(true), &Options::default(), &mut out, &mut Cursor::new(archive));
+        // the first corruption is reported, the members behind it are read
+        let corruption = corruption.unwrap();
+        assert!(corruption.decoded > 0 && corruption.decoded < lines.len() as u64);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("stdin!f1.log.gz:2023-01-26 09:32:28,828 INFO f1 line 0\n"));
+        assert!(out.ends_with("stdin!f2.log:2023-01-26 09:33:28,828 INFO f2\nstdin!f3.log:2023-01-26 09:34:28,828 INFO f3\n"));
     }
 }
@@ -0,0 +1,125 @@
+use chrono::naive::NaiveDateTime;
+use chrono::offset::LocalResult;
+use chrono::{Duration, FixedOffset, Local, Offset, TimeZone};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+use crate::{normalize_bits, to_naive, DEFAULT_PIVOT_YEAR};
+
+/// Time zone of log lines or of the start/end date
+#[derive(Clone, Debug)]
+pub enum Zone {
+    /// the system zone, honors TZ
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    /// local, UTC, +01:00, Europe/Berlin
+    fn from_str(s: &str) -> Result<Zone, String> {
+        match s {
+            "local" | "Local" => return Ok(Zone::Local),
+            "UTC" | "utc" | "Z" => return Ok(Zone::Utc),
+            _ => (),
+        }
+        if s.starts_with(['+', '-']) {
+            let fixed = chrono::DateTime::parse_from_str(&format!("2000-01-01 00:00:00 {s}"), "%Y-%m-%d %H:%M:%S %:z")
+                .map_err(|_| format!("invalid offset: {s}"))?;
+            return Ok(Zone::Fixed(*fixed.offset()));
+        }
+        s.parse::<Tz>().map(Zone::Named).map_err(|_| format!("unknown time zone: {s}"))
+    }
+}
+
+/// What to do with local times which are ambiguous (fall-back hour)
+/// or don't exist (spring-forward gap) in a zone
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DstPolicy {
+    /// the first occurrence
+    Earliest,
+    /// the second occurrence
+    Latest,
+    /// treat the line as unparseable
+    Reject,
+    /// follow the order of the lines: once the log went past the first
+    /// occurrence, the second one is taken
+    Auto,
+}
+
+impl FromStr for DstPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DstPolicy, String> {
+        match s {
+            "earliest" => Ok(DstPolicy::Earliest),
+            "latest" => Ok(DstPolicy::Latest),
+            "reject" => Ok(DstPolicy::Reject),
+            "auto" => Ok(DstPolicy::Auto),
+            _ => Err(format!("unknown dst policy: {s} (earliest, latest, reject, auto)")),
+        }
+    }
+}
+
+impl Zone {
+    /// Convert a packed local date value to a packed UTC date value.
+    /// `previous` is the UTC value of the line before, used by `DstPolicy::Auto`.
+    pub fn to_utc(&self, date_value: u64, policy: DstPolicy, previous: u64) -> Option<u64> {
+        let naive = to_naive(date_value)?;
+        let previous = to_naive(previous);
+        let utc = match self {
+            Zone::Local => resolve(&Local, &naive, policy, previous),
+            Zone::Utc => Some(naive),
+            Zone::Fixed(offset) => resolve(offset, &naive, policy, previous),
+            Zone::Named(tz) => resolve(tz, &naive, policy, previous),
+        }?;
+        Some(normalize_bits(utc, DEFAULT_PIVOT_YEAR))
+    }
+
+    /// Convert a packed UTC date value to the wall clock of the zone
+    pub fn from_utc(&self, date_value: u64) -> Option<u64> {
+        let naive = to_naive(date_value)?;
+        let local = match self {
+            Zone::Local => Local.from_utc_datetime(&naive).naive_local(),
+            Zone::Utc => naive,
+            Zone::Fixed(offset) => offset.from_utc_datetime(&naive).naive_local(),
+            Zone::Named(tz) => tz.from_utc_datetime(&naive).naive_local(),
+        };
+        Some(normalize_bits(local, DEFAULT_PIVOT_YEAR))
+    }
+}
+
+fn resolve<T: TimeZone>(
+    tz: &T,
+    naive: &NaiveDateTime,
+    policy: DstPolicy,
+    previous: Option<NaiveDateTime>,
+) -> Option<NaiveDateTime> {
+    match tz.from_local_datetime(naive) {
+        LocalResult::Single(dt) => Some(dt.naive_utc()),
+        LocalResult::Ambiguous(a, b) => {
+            let (earliest, latest) = (a.naive_utc().min(b.naive_utc()), a.naive_utc().max(b.naive_utc()));
+            match policy {
+                DstPolicy::Earliest => Some(earliest),
+                DstPolicy::Latest => Some(latest),
+                DstPolicy::Reject => None,
+                DstPolicy::Auto => match previous {
+                    // more than half the shift behind the first occurrence, we are in the repeated hour
+                    Some(previous) if previous > earliest + (latest - earliest) / 2 => Some(latest),
+                    _ => Some(earliest),
+                },
+            }
+        }
+        LocalResult::None => {
+            if policy == DstPolicy::Reject {
+                return None;
+            }
+            // in the gap, shifted forward by the offset in effect before the switch
+            let before = tz.offset_from_local_datetime(&(*naive - Duration::days(1))).earliest()?;
+            Some(*naive - Duration::seconds(before.fix().local_minus_utc() as i64))
+        }
+    }
+}